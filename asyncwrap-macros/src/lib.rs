@@ -3,14 +3,19 @@
 //! This crate provides two main macros:
 //! - `#[async_wrap]` - Marks a method for async wrapper generation
 //! - `#[blocking_impl(AsyncType)]` - Processes an impl block and generates async wrappers
+//!
+//! and their mirror for the opposite direction:
+//! - `#[blocking_wrap]` - Marks an async method for blocking wrapper generation
+//! - `#[async_impl(BlockingType)]` - Processes an impl block and generates blocking wrappers
 
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
-use quote::quote;
+use quote::{quote, quote_spanned};
 use syn::{
     parse::{Parse, ParseStream},
-    parse_macro_input, FnArg, Ident, ImplItem, ImplItemFn, ItemImpl, Pat, ReturnType, Token, Type,
-    Visibility,
+    parse_macro_input,
+    spanned::Spanned,
+    FnArg, Ident, ImplItem, ImplItemFn, ItemImpl, Pat, ReturnType, Token, Type, Visibility,
 };
 
 #[derive(Clone, Copy, Default)]
@@ -25,12 +30,40 @@ enum Strategy {
 /// This attribute should be placed on public methods within a `#[blocking_impl]` block.
 /// The method will have an async version generated in the corresponding async wrapper struct.
 ///
+/// On a free function, it generates a sibling `async fn <name>_async` instead, and on the
+/// functions of a `#[blocking_mod]` module an async function in its `r#async` submodule.
+///
 /// # Requirements
 ///
 /// - The method must take `&self` (not `&mut self` or `self`)
 /// - All arguments must be `Send + 'static` to cross the `spawn_blocking` boundary
 /// - The method must not be async
 ///
+/// # Options
+///
+/// - `catch_unwind = bool` - Overrides the impl-level `catch_unwind` setting for this method
+/// - `assert_unwind_safe = bool` - Whether the call is wrapped in `AssertUnwindSafe` before
+///   `catch_unwind`; set to `false` to require the receiver and arguments to be `UnwindSafe`
+/// - `retry(max = 3, backoff = "exponential", base = "50ms", when = path)` - Re-runs the
+///   blocking call while it returns an error accepted by `when` (a `fn(&E) -> bool`; every
+///   error if omitted), sleeping asynchronously between attempts. `max` counts all attempts,
+///   `backoff` is `"fixed"`, `"linear"` or `"exponential"`. Requires a `Result` return type and
+///   `Clone` arguments
+/// - `warn_after = "10ms"`, `on_slow = path`, `panic_on_slow = bool` - Override the impl-level
+///   slow-call settings for this method
+/// - `cache(ttl = "30s", capacity = 1000)` - Reuses the result of an earlier successful call
+///   with equal arguments, kept in the wrapper's `asyncwrap::WrapState` field. Generates
+///   `invalidate_<method>()` and `clear_cache()`. Requires `Hash + Eq + Clone` arguments and a
+///   `Clone` return type
+/// - `batch(with = get_many, max_size = 64, max_delay = "1ms")` - Groups concurrent calls of a
+///   one-argument method into one blocking call of `get_many(Vec<Arg>) -> Vec<Output>`, kept in
+///   the wrapper's `asyncwrap::WrapState` field. Requires strategy `"spawn_blocking"`
+/// - `priority = "high" | "normal" | "low"` - The priority of this method's calls in the
+///   impl-level `dispatcher` (default `"normal"`). Generates `<method>_with_priority(priority,
+///   ...)` to choose the priority per call
+/// - `strategy = "spawn_blocking" | "block_in_place"` - The strategy of a free function or a
+///   function in a `#[blocking_mod]` module; methods use the `#[blocking_impl]` setting
+///
 /// # Example
 ///
 /// ```ignore
@@ -43,331 +76,3822 @@ enum Strategy {
 /// }
 /// ```
 #[proc_macro_attribute]
-pub fn async_wrap(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn async_wrap(attr: TokenStream, item: TokenStream) -> TokenStream {
     let Ok(method) = syn::parse::<ImplItemFn>(item.clone()) else {
         return item;
     };
 
-    if let Err(e) = validate_async_wrap_method(&method) {
-        return e.to_compile_error().into();
+    let is_method = matches!(method.sig.inputs.first(), Some(FnArg::Receiver(_)));
+    if is_method || method.sig.asyncness.is_some() {
+        if let Err(e) = validate_async_wrap_method(&method.sig) {
+            return e.to_compile_error().into();
+        }
+        return item;
     }
 
-    item
+    let method_args = parse_macro_input!(attr as MethodArgs);
+    let name = &method.sig.ident;
+    let kind = CallKind::Function {
+        path: quote! { #name },
+        async_name: quote::format_ident!("{}_async", name),
+    };
+    let wrapper = default_settings().and_then(|args| {
+        generate_async_function(
+            kind,
+            &method.sig,
+            method.vis.clone(),
+            &method.attrs,
+            method_args,
+            &args,
+        )
+    });
+
+    let item = TokenStream2::from(item);
+    match wrapper {
+        Ok(wrapper) => quote! { #item #wrapper }.into(),
+        Err(e) => {
+            let error = e.to_compile_error();
+            quote! { #item #error }.into()
+        }
+    }
+}
+
+/// Settings for free functions, which have no `#[blocking_impl]` to inherit from.
+fn default_settings() -> syn::Result<BlockingImplArgs> {
+    syn::parse::Parser::parse2(
+        |input: ParseStream<'_>| {
+            BlockingImplArgs::parse_settings(syn::parse_quote!(r#async), input)
+        },
+        TokenStream2::new(),
+    )
+}
+
+fn generate_async_function(
+    kind: CallKind,
+    sig: &syn::Signature,
+    visibility: Visibility,
+    attrs: &[syn::Attribute],
+    method_args: MethodArgs,
+    args: &BlockingImplArgs,
+) -> syn::Result<TokenStream2> {
+    if sig.asyncness.is_some() {
+        return Err(syn::Error::new_spanned(
+            sig.asyncness,
+            "#[async_wrap] cannot be used on async functions",
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            "#[async_wrap] does not support generic functions",
+        ));
+    }
+
+    for (option, field) in [("dispatcher", &args.dispatcher), ("handle", &args.handle)] {
+        if let Some(field) = field {
+            return Err(syn::Error::new_spanned(
+                field,
+                format!("`{option}` is only supported on methods"),
+            ));
+        }
+    }
+    let method_only = method_args
+        .subscribe
+        .as_ref()
+        .map(|subscribe| &subscribe.keyword)
+        .or(method_args.coalesce.as_ref())
+        .or(method_args.cache.as_ref().map(|cache| &cache.keyword))
+        .or(method_args.batch.as_ref().map(|batch| &batch.keyword));
+    if let Some(priority) = &method_args.priority {
+        return Err(syn::Error::new_spanned(
+            priority,
+            "`priority` is only supported on methods",
+        ));
+    }
+    if let Some(keyword) = method_only {
+        return Err(syn::Error::new_spanned(
+            keyword,
+            format!("`{keyword}` is only supported on methods"),
+        ));
+    }
+
+    let options = resolve_options(method_args, sig, args)?;
+    let info = signature_info(kind, sig, visibility, attrs);
+    Ok(generate_async_method(&info, &options, args))
 }
 
+// Each flag is a separate `#[blocking_impl]` setting.
+#[allow(clippy::struct_excessive_bools)]
 struct BlockingImplArgs {
     async_type: Type,
     strategy: Strategy,
     field: Ident,
+    catch_unwind: bool,
+    assert_unwind_safe: bool,
+    circuit_breaker: Option<CircuitBreakerArgs>,
+    observer: Option<syn::Path>,
+    slow_call: SlowCallArgs,
+    state: Ident,
+    pool: Option<syn::LitStr>,
+    thread_init: Option<syn::Path>,
+    thread_deinit: Option<syn::Path>,
+    dispatcher: Option<Ident>,
+    handle: Option<Ident>,
+    generate_mock: bool,
+    fault_injection: bool,
 }
 
 impl Parse for BlockingImplArgs {
     fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
         let async_type: Type = input.parse()?;
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+        Self::parse_settings(async_type, input)
+    }
+}
 
+impl BlockingImplArgs {
+    /// Parses the comma-separated `key = value` settings that follow the async type, up to
+    /// the end of the input or the item a function-like macro takes after them.
+    fn parse_settings(async_type: Type, input: ParseStream<'_>) -> syn::Result<Self> {
         let mut strategy = Strategy::default();
         let mut field = Ident::new("inner", proc_macro2::Span::call_site());
+        let mut catch_unwind = None;
+        let mut assert_unwind_safe = true;
+        let mut circuit_breaker = None;
+        let mut observer = None;
+        let mut slow_call = SlowCallArgs::default();
+        let mut state = Ident::new("state", proc_macro2::Span::call_site());
+        let mut pool: Option<syn::LitStr> = None;
+        let mut thread_init = None;
+        let mut thread_deinit: Option<syn::Path> = None;
+        let mut dispatcher = None;
+        let mut handle = None;
+        let mut generate_mock = false;
+        let mut fault_injection = false;
 
-        while input.peek(Token![,]) {
-            input.parse::<Token![,]>()?;
+        while !at_settings_end(input) {
             let ident: Ident = input.parse()?;
-            input.parse::<Token![=]>()?;
 
-            if ident == "strategy" {
-                let value: syn::LitStr = input.parse()?;
-                strategy = match value.value().as_str() {
-                    "spawn_blocking" => Strategy::SpawnBlocking,
-                    "block_in_place" => Strategy::BlockInPlace,
-                    other => {
-                        return Err(syn::Error::new_spanned(
-                            value,
-                            format!(
-                                "unknown strategy \"{other}\", expected \"spawn_blocking\" or \"block_in_place\""
-                            ),
-                        ))
-                    }
-                };
-            } else if ident == "field" {
-                let value: syn::LitStr = input.parse()?;
-                field = Ident::new(&value.value(), value.span());
+            if ident == "circuit_breaker" {
+                let content;
+                syn::parenthesized!(content in input);
+                circuit_breaker = Some(CircuitBreakerArgs::parse(&content)?);
             } else {
-                return Err(syn::Error::new_spanned(
-                    ident,
-                    "expected `strategy` or `field`",
-                ));
+                input.parse::<Token![=]>()?;
+
+                if ident == "strategy" {
+                    strategy = parse_strategy(&input.parse()?)?;
+                } else if ident == "field" {
+                    let value: syn::LitStr = input.parse()?;
+                    field = Ident::new(&value.value(), value.span());
+                } else if ident == "catch_unwind" {
+                    let value: syn::LitBool = input.parse()?;
+                    catch_unwind = Some(value);
+                } else if ident == "assert_unwind_safe" {
+                    let value: syn::LitBool = input.parse()?;
+                    assert_unwind_safe = value.value;
+                } else if ident == "observer" {
+                    observer = Some(input.parse()?);
+                } else if ident == "state" {
+                    let value: syn::LitStr = input.parse()?;
+                    state = Ident::new(&value.value(), value.span());
+                } else if ident == "pool" {
+                    pool = Some(input.parse()?);
+                } else if ident == "thread_init" {
+                    thread_init = Some(input.parse()?);
+                } else if ident == "thread_deinit" {
+                    thread_deinit = Some(input.parse()?);
+                } else if ident == "dispatcher" {
+                    let value: syn::LitStr = input.parse()?;
+                    dispatcher = Some(Ident::new(&value.value(), value.span()));
+                } else if ident == "handle" {
+                    let value: syn::LitStr = input.parse()?;
+                    handle = Some(Ident::new(&value.value(), value.span()));
+                } else if ident == "generate_mock" {
+                    generate_mock = input.parse::<syn::LitBool>()?.value;
+                } else if ident == "fault_injection" {
+                    fault_injection = input.parse::<syn::LitBool>()?.value;
+                } else if !slow_call.parse_option(&ident, input)? {
+                    return Err(syn::Error::new_spanned(
+                        ident,
+                        "expected `strategy`, `field`, `catch_unwind`, `assert_unwind_safe`, \
+                         `observer`, `state`, `pool`, `thread_init`, `thread_deinit`, \
+                         `dispatcher`, `handle`, `generate_mock`, `fault_injection`, \
+                         `warn_after`, `on_slow`, `panic_on_slow` or `circuit_breaker(...)`",
+                    ));
+                }
+            }
+
+            if at_settings_end(input) {
+                break;
             }
+            input.parse::<Token![,]>()?;
+        }
+
+        if let Some(value) = &catch_unwind {
+            check_catch_unwind_strategy(value, strategy)?;
+        }
+        if let (Some(deinit), None) = (&thread_deinit, &thread_init) {
+            return Err(syn::Error::new_spanned(
+                deinit,
+                "`thread_deinit` requires `thread_init`",
+            ));
         }
+        check_runtime_options(strategy, pool.as_ref(), handle.as_ref())?;
 
         Ok(BlockingImplArgs {
             async_type,
             strategy,
             field,
+            catch_unwind: catch_unwind.is_some_and(|value| value.value),
+            assert_unwind_safe,
+            circuit_breaker,
+            observer,
+            slow_call,
+            state,
+            pool,
+            thread_init,
+            thread_deinit,
+            dispatcher,
+            handle,
+            generate_mock,
+            fault_injection,
         })
     }
 }
 
-struct MethodInfo {
-    name: Ident,
-    visibility: Visibility,
-    args: Vec<(Ident, Type)>,
-    return_type: Option<Type>,
-    is_result: bool,
-    doc_attrs: Vec<syn::Attribute>,
+/// Checks that the runtime `pool` or `handle` is only set with strategy `spawn_blocking`, and
+/// not both at once.
+fn check_runtime_options(
+    strategy: Strategy,
+    pool: Option<&syn::LitStr>,
+    handle: Option<&Ident>,
+) -> syn::Result<()> {
+    if let (Some(pool), Strategy::BlockInPlace) = (pool, strategy) {
+        return Err(syn::Error::new_spanned(
+            pool,
+            "`pool` requires strategy \"spawn_blocking\"",
+        ));
+    }
+    if let Some(handle) = handle {
+        if matches!(strategy, Strategy::BlockInPlace) {
+            return Err(syn::Error::new_spanned(
+                handle,
+                "`handle` requires strategy \"spawn_blocking\"",
+            ));
+        }
+        if pool.is_some() {
+            return Err(syn::Error::new_spanned(
+                handle,
+                "`handle` cannot be combined with `pool`",
+            ));
+        }
+    }
+    Ok(())
 }
 
-fn has_async_wrap_attr(method: &ImplItemFn) -> bool {
-    method
-        .attrs
-        .iter()
-        .any(|attr| attr.path().is_ident("async_wrap"))
+/// Whether `input` is past the settings: at its end, or at the `{ ... }` block of
+/// `wrap_foreign!` or the `extern` block of `wrap_extern!`.
+fn at_settings_end(input: ParseStream<'_>) -> bool {
+    input.is_empty()
+        || input.peek(syn::token::Brace)
+        || input.peek(Token![extern])
+        || input.peek(Token![#])
 }
 
-fn remove_async_wrap_attr(method: &mut ImplItemFn) {
-    method
-        .attrs
-        .retain(|attr| !attr.path().is_ident("async_wrap"));
+fn parse_strategy(value: &syn::LitStr) -> syn::Result<Strategy> {
+    match value.value().as_str() {
+        "spawn_blocking" => Ok(Strategy::SpawnBlocking),
+        "block_in_place" => Ok(Strategy::BlockInPlace),
+        other => Err(syn::Error::new_spanned(
+            value,
+            format!(
+                "unknown strategy \"{other}\", expected \"spawn_blocking\" or \"block_in_place\""
+            ),
+        )),
+    }
 }
 
-fn is_self_by_ref(arg: &FnArg) -> bool {
-    matches!(arg, FnArg::Receiver(r) if r.reference.is_some() && r.mutability.is_none())
+/// The `warn_after`, `on_slow` and `panic_on_slow` options, accepted both by
+/// `#[blocking_impl]` and by `#[async_wrap]`.
+#[derive(Default)]
+struct SlowCallArgs {
+    warn_after_nanos: Option<u64>,
+    on_slow: Option<syn::Path>,
+    panic_on_slow: Option<syn::LitBool>,
 }
 
-fn validate_async_wrap_method(method: &ImplItemFn) -> syn::Result<()> {
-    if method.sig.asyncness.is_some() {
-        return Err(syn::Error::new_spanned(
-            method.sig.asyncness,
-            "#[async_wrap] cannot be used on async methods",
-        ));
+impl SlowCallArgs {
+    /// Parses the value of `ident` if it is one of the slow-call options, after its `=`.
+    fn parse_option(&mut self, ident: &Ident, input: ParseStream<'_>) -> syn::Result<bool> {
+        if ident == "warn_after" {
+            self.warn_after_nanos = Some(parse_duration(&input.parse()?)?);
+        } else if ident == "on_slow" {
+            self.on_slow = Some(input.parse()?);
+        } else if ident == "panic_on_slow" {
+            self.panic_on_slow = Some(input.parse()?);
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
     }
+}
 
-    match method.sig.inputs.first() {
-        Some(arg) if is_self_by_ref(arg) => Ok(()),
-        Some(FnArg::Receiver(r)) if r.mutability.is_some() => Err(syn::Error::new_spanned(
-            r,
-            "#[async_wrap] requires `&self`, not `&mut self`",
-        )),
-        Some(FnArg::Receiver(r)) if r.reference.is_none() => Err(syn::Error::new_spanned(
-            r,
-            "#[async_wrap] requires `&self`, not `self`",
-        )),
-        Some(arg) => Err(syn::Error::new_spanned(
-            arg,
-            "#[async_wrap] requires methods taking `&self`",
-        )),
-        None => Err(syn::Error::new_spanned(
-            &method.sig,
-            "#[async_wrap] requires methods taking `&self`",
-        )),
-    }
+/// Slow-call detection settings of a method with an effective `warn_after`.
+struct SlowCallOptions {
+    threshold_nanos: u64,
+    on_slow: Option<syn::Path>,
+    panic_on_slow: bool,
 }
 
-fn is_result_type(ty: &Type) -> bool {
-    if let Type::Path(type_path) = ty {
-        if let Some(segment) = type_path.path.segments.last() {
-            return segment.ident == "Result";
-        }
-    }
-    false
+/// Options of `#[blocking_impl(..., circuit_breaker(...))]`.
+struct CircuitBreakerArgs {
+    failure_threshold: u32,
+    open_for_nanos: u64,
+    half_open_probes: u32,
+    field: Ident,
 }
 
-fn extract_method_info(method: &ImplItemFn) -> Option<MethodInfo> {
-    let first_arg = method.sig.inputs.first()?;
-    if !is_self_by_ref(first_arg) {
-        return None;
-    }
+impl CircuitBreakerArgs {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let mut breaker = CircuitBreakerArgs {
+            failure_threshold: 5,
+            open_for_nanos: 30_000_000_000,
+            half_open_probes: 1,
+            field: Ident::new("breaker", proc_macro2::Span::call_site()),
+        };
 
-    let name = method.sig.ident.clone();
-    let visibility = method.vis.clone();
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
 
-    let args: Vec<(Ident, Type)> = method
-        .sig
-        .inputs
-        .iter()
-        .filter_map(|arg| {
-            if let FnArg::Typed(pat_type) = arg {
-                if let Pat::Ident(pat_ident) = &*pat_type.pat {
-                    return Some((pat_ident.ident.clone(), (*pat_type.ty).clone()));
+            if ident == "failure_threshold" || ident == "half_open_probes" {
+                let value: syn::LitInt = input.parse()?;
+                let count: u32 = value.base10_parse()?;
+                if count == 0 {
+                    return Err(syn::Error::new_spanned(
+                        value,
+                        format!("`{ident}` must be at least 1"),
+                    ));
+                }
+                if ident == "failure_threshold" {
+                    breaker.failure_threshold = count;
+                } else {
+                    breaker.half_open_probes = count;
                 }
+            } else if ident == "open_for" {
+                breaker.open_for_nanos = parse_duration(&input.parse()?)?;
+            } else if ident == "field" {
+                let value: syn::LitStr = input.parse()?;
+                breaker.field = Ident::new(&value.value(), value.span());
+            } else {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "expected `failure_threshold`, `open_for`, `half_open_probes` or `field`",
+                ));
             }
-            None
-        })
-        .collect();
 
-    let (return_type, is_result) = match &method.sig.output {
-        ReturnType::Default => (None, false),
-        ReturnType::Type(_, ty) => (Some((**ty).clone()), is_result_type(ty)),
-    };
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
 
-    let doc_attrs: Vec<_> = method
-        .attrs
-        .iter()
-        .filter(|attr| attr.path().is_ident("doc"))
-        .cloned()
-        .collect();
+        Ok(breaker)
+    }
+}
 
-    Some(MethodInfo {
-        name,
-        visibility,
-        args,
-        return_type,
-        is_result,
-        doc_attrs,
-    })
+/// Options accepted by `#[async_wrap(...)]` on a method or a free function.
+///
+/// Every option is optional; unset options fall back to the impl-level setting.
+#[derive(Default)]
+struct MethodArgs {
+    strategy: Option<syn::LitStr>,
+    catch_unwind: Option<syn::LitBool>,
+    assert_unwind_safe: Option<bool>,
+    retry: Option<RetryArgs>,
+    slow_call: SlowCallArgs,
+    subscribe: Option<SubscribeArgs>,
+    coalesce: Option<Ident>,
+    cache: Option<CacheArgs>,
+    batch: Option<BatchArgs>,
+    priority: Option<syn::LitStr>,
 }
 
-fn generate_async_method(info: &MethodInfo, strategy: Strategy, field: &Ident) -> TokenStream2 {
-    let name = &info.name;
-    let vis = &info.visibility;
-    let doc_attrs = &info.doc_attrs;
-    let arg_names: Vec<_> = info.args.iter().map(|(name, _)| name).collect();
-    let arg_types: Vec<_> = info.args.iter().map(|(_, ty)| ty).collect();
+impl Parse for MethodArgs {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let mut args = MethodArgs::default();
 
-    match strategy {
-        Strategy::SpawnBlocking => {
-            let spawn_call = quote! {
-                let __asyncwrap_inner = ::std::sync::Arc::clone(&self.#field);
-                ::tokio::task::spawn_blocking(move || __asyncwrap_inner.#name(#(#arg_names),*)).await
-            };
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
 
-            let (return_type, body) = if info.is_result {
-                let inner_return = info.return_type.as_ref().unwrap();
-                (
-                    quote! { -> ::asyncwrap::AsyncWrapResult<#inner_return> },
-                    quote! {
-                        #spawn_call
-                            .map_err(::asyncwrap::AsyncWrapError::TaskFailed)?
-                            .map_err(::asyncwrap::AsyncWrapError::Inner)
-                    },
-                )
+            if ident == "retry" {
+                let content;
+                syn::parenthesized!(content in input);
+                args.retry = Some(RetryArgs::parse(&content, ident)?);
+            } else if ident == "subscribe" {
+                args.subscribe = Some(if input.peek(syn::token::Paren) {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    SubscribeArgs::parse(&content, ident)?
+                } else {
+                    SubscribeArgs::new(ident)
+                });
+            } else if ident == "coalesce" {
+                args.coalesce = Some(ident);
+            } else if ident == "batch" {
+                let content;
+                syn::parenthesized!(content in input);
+                args.batch = Some(BatchArgs::parse(&content, ident)?);
+            } else if ident == "cache" {
+                args.cache = Some(if input.peek(syn::token::Paren) {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    CacheArgs::parse(&content, ident)?
+                } else {
+                    CacheArgs::new(ident)
+                });
+            } else if ident == "catch_unwind" {
+                input.parse::<Token![=]>()?;
+                args.catch_unwind = Some(input.parse()?);
+            } else if ident == "assert_unwind_safe" {
+                input.parse::<Token![=]>()?;
+                let value: syn::LitBool = input.parse()?;
+                args.assert_unwind_safe = Some(value.value);
+            } else if ident == "strategy" {
+                input.parse::<Token![=]>()?;
+                let value: syn::LitStr = input.parse()?;
+                parse_strategy(&value)?;
+                args.strategy = Some(value);
+            } else if ident == "priority" {
+                input.parse::<Token![=]>()?;
+                let value: syn::LitStr = input.parse()?;
+                parse_priority(&value)?;
+                args.priority = Some(value);
             } else {
-                let ret_ty = info
-                    .return_type
-                    .as_ref()
-                    .map_or_else(|| quote! { () }, |ty| quote! { #ty });
-                (
-                    quote! { -> ::core::result::Result<#ret_ty, ::tokio::task::JoinError> },
-                    spawn_call,
-                )
-            };
-
-            quote! {
-                #(#doc_attrs)*
-                #[must_use = "futures do nothing unless you `.await` or poll them"]
-                #vis async fn #name(&self, #(#arg_names: #arg_types),*) #return_type {
-                    #body
+                input.parse::<Token![=]>()?;
+                if !args.slow_call.parse_option(&ident, input)? {
+                    return Err(syn::Error::new_spanned(
+                        ident,
+                        "expected `strategy`, `catch_unwind`, `assert_unwind_safe`, \
+                         `warn_after`, `on_slow`, `panic_on_slow`, `priority`, `coalesce`, \
+                         `cache(...)`, `batch(...)`, `retry(...)` or `subscribe(...)`",
+                    ));
                 }
             }
-        }
-        Strategy::BlockInPlace => {
-            let return_type = info
-                .return_type
-                .as_ref()
-                .map(|ty| quote! { -> #ty });
 
-            quote! {
-                #(#doc_attrs)*
-                #[must_use = "futures do nothing unless you `.await` or poll them"]
-                #vis async fn #name(&self, #(#arg_names: #arg_types),*) #return_type {
-                    ::tokio::task::block_in_place(|| self.#field.#name(#(#arg_names),*))
-                }
+            if input.is_empty() {
+                break;
             }
+            input.parse::<Token![,]>()?;
         }
+
+        Ok(args)
     }
 }
 
-/// Processes an impl block and generates async wrappers for marked methods.
-///
-/// # Arguments
-///
-/// The attribute takes the name of the async wrapper struct as an argument.
-///
-/// # Example
-///
-/// ```ignore
-/// use asyncwrap::blocking_impl;
-/// use std::sync::Arc;
-///
-/// struct BlockingClient {
-///     // fields
-/// }
-///
-/// #[blocking_impl(AsyncClient)]
-/// impl BlockingClient {
-///     #[async_wrap]
-///     pub fn fetch(&self, id: u32) -> Result<String, Error> {
-///         // blocking implementation
-///     }
-/// }
-///
-/// // You still need to define the async struct:
-/// pub struct AsyncClient {
-///     inner: Arc<BlockingClient>,
-/// }
-///
-/// // The macro generates:
-/// // impl AsyncClient {
-/// //     pub async fn fetch(&self, id: u32) -> Result<String, AsyncWrapError<Error>> {
-/// //         let inner = Arc::clone(&self.inner);
-/// //         tokio::task::spawn_blocking(move || inner.fetch(id))
-/// //             .await
-/// //             .map_err(AsyncWrapError::from)?
-/// //     }
-/// // }
-/// ```
-#[proc_macro_attribute]
-pub fn blocking_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let args = parse_macro_input!(attr as BlockingImplArgs);
-    let mut input = parse_macro_input!(item as ItemImpl);
-
-    let async_type = &args.async_type;
+/// Options of `#[async_wrap(retry(...))]`.
+#[derive(Clone)]
+struct RetryArgs {
+    keyword: Ident,
+    max_attempts: u32,
+    backoff: Ident,
+    base_nanos: u64,
+    when: Option<syn::Path>,
+}
 
-    let generics = &input.generics;
-    let where_clause = &input.generics.where_clause;
-    let generic_params: Vec<_> = generics.params.iter().collect();
+impl RetryArgs {
+    fn parse(input: ParseStream<'_>, keyword: Ident) -> syn::Result<Self> {
+        let mut retry = RetryArgs {
+            keyword,
+            max_attempts: 3,
+            backoff: Ident::new("Exponential", proc_macro2::Span::call_site()),
+            base_nanos: 100_000_000,
+            when: None,
+        };
 
-    let mut async_methods = Vec::new();
-    let mut errors = Vec::new();
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
 
-    for item in &mut input.items {
-        if let ImplItem::Fn(method) = item {
-            if has_async_wrap_attr(method) {
-                if let Err(e) = validate_async_wrap_method(method) {
-                    errors.push(e);
-                } else if let Some(info) = extract_method_info(method) {
-                    async_methods.push(generate_async_method(&info, args.strategy, &args.field));
+            if ident == "max" {
+                let value: syn::LitInt = input.parse()?;
+                retry.max_attempts = value.base10_parse()?;
+                if retry.max_attempts == 0 {
+                    return Err(syn::Error::new_spanned(value, "`max` must be at least 1"));
                 }
-                remove_async_wrap_attr(method);
-            }
-        }
-    }
-
-    if !errors.is_empty() {
-        let compile_errors = errors.into_iter().map(|e| e.to_compile_error());
-        return quote! {
-            #input
+            } else if ident == "backoff" {
+                let value: syn::LitStr = input.parse()?;
+                let variant = match value.value().as_str() {
+                    "fixed" => "Fixed",
+                    "linear" => "Linear",
+                    "exponential" => "Exponential",
+                    other => {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            format!(
+                                "unknown backoff \"{other}\", expected \"fixed\", \"linear\" or \"exponential\""
+                            ),
+                        ))
+                    }
+                };
+                retry.backoff = Ident::new(variant, value.span());
+            } else if ident == "base" {
+                retry.base_nanos = parse_duration(&input.parse()?)?;
+            } else if ident == "when" {
+                retry.when = Some(input.parse()?);
+            } else {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "expected `max`, `backoff`, `base` or `when`",
+                ));
+            }
+
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+
+        Ok(retry)
+    }
+}
+
+/// Options of `#[async_wrap(subscribe(...))]`.
+struct SubscribeArgs {
+    keyword: Ident,
+    unsubscribe: Option<Ident>,
+    capacity: usize,
+    overflow: Ident,
+}
+
+impl SubscribeArgs {
+    fn new(keyword: Ident) -> Self {
+        SubscribeArgs {
+            keyword,
+            unsubscribe: None,
+            capacity: 64,
+            overflow: Ident::new("DropOldest", proc_macro2::Span::call_site()),
+        }
+    }
+
+    fn parse(input: ParseStream<'_>, keyword: Ident) -> syn::Result<Self> {
+        let mut subscribe = SubscribeArgs::new(keyword);
+
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+
+            if ident == "unsubscribe" {
+                let value: syn::LitStr = input.parse()?;
+                subscribe.unsubscribe = Some(Ident::new(&value.value(), value.span()));
+            } else if ident == "capacity" {
+                let value: syn::LitInt = input.parse()?;
+                subscribe.capacity = value.base10_parse()?;
+                if subscribe.capacity == 0 {
+                    return Err(syn::Error::new_spanned(
+                        value,
+                        "`capacity` must be at least 1",
+                    ));
+                }
+            } else if ident == "overflow" {
+                let value: syn::LitStr = input.parse()?;
+                let variant = match value.value().as_str() {
+                    "drop_oldest" => "DropOldest",
+                    "drop_newest" => "DropNewest",
+                    "block" => "Block",
+                    other => {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            format!(
+                                "unknown overflow policy \"{other}\", expected \"drop_oldest\", \"drop_newest\" or \"block\""
+                            ),
+                        ))
+                    }
+                };
+                subscribe.overflow = Ident::new(variant, value.span());
+            } else {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "expected `unsubscribe`, `capacity` or `overflow`",
+                ));
+            }
+
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+
+        Ok(subscribe)
+    }
+}
+
+/// Options of `#[async_wrap(cache(...))]`.
+struct CacheArgs {
+    keyword: Ident,
+    ttl_nanos: Option<u64>,
+    capacity: usize,
+}
+
+impl CacheArgs {
+    fn new(keyword: Ident) -> Self {
+        CacheArgs {
+            keyword,
+            ttl_nanos: None,
+            capacity: 1000,
+        }
+    }
+
+    fn parse(input: ParseStream<'_>, keyword: Ident) -> syn::Result<Self> {
+        let mut cache = CacheArgs::new(keyword);
+
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+
+            if ident == "ttl" {
+                cache.ttl_nanos = Some(parse_duration(&input.parse()?)?);
+            } else if ident == "capacity" {
+                let value: syn::LitInt = input.parse()?;
+                cache.capacity = value.base10_parse()?;
+                if cache.capacity == 0 {
+                    return Err(syn::Error::new_spanned(
+                        value,
+                        "`capacity` must be at least 1",
+                    ));
+                }
+            } else {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "expected `ttl` or `capacity`",
+                ));
+            }
+
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+
+        Ok(cache)
+    }
+}
+
+/// Options of `#[async_wrap(batch(...))]`.
+struct BatchArgs {
+    keyword: Ident,
+    with: Ident,
+    max_size: usize,
+    max_delay_nanos: u64,
+}
+
+impl BatchArgs {
+    fn parse(input: ParseStream<'_>, keyword: Ident) -> syn::Result<Self> {
+        let mut with = None;
+        let mut max_size = 64;
+        let mut max_delay_nanos = 1_000_000;
+
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+
+            if ident == "with" {
+                with = Some(input.parse()?);
+            } else if ident == "max_size" {
+                let value: syn::LitInt = input.parse()?;
+                max_size = value.base10_parse()?;
+                if max_size == 0 {
+                    return Err(syn::Error::new_spanned(
+                        value,
+                        "`max_size` must be at least 1",
+                    ));
+                }
+            } else if ident == "max_delay" {
+                max_delay_nanos = parse_duration(&input.parse()?)?;
+            } else {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "expected `with`, `max_size` or `max_delay`",
+                ));
+            }
+
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+
+        let Some(with) = with else {
+            return Err(syn::Error::new_spanned(
+                keyword,
+                "`batch` requires `with = <batch method>`",
+            ));
+        };
+        Ok(BatchArgs {
+            keyword,
+            with,
+            max_size,
+            max_delay_nanos,
+        })
+    }
+}
+
+/// Parses a duration literal such as `"50ms"` or `"2s"` into nanoseconds.
+fn parse_duration(lit: &syn::LitStr) -> syn::Result<u64> {
+    let value = lit.value();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+
+    let multiplier: u64 = match unit.trim() {
+        "ns" => 1,
+        "us" => 1_000,
+        "ms" => 1_000_000,
+        "s" => 1_000_000_000,
+        "m" => 60 * 1_000_000_000,
+        "h" => 60 * 60 * 1_000_000_000,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                lit,
+                format!(
+                    "invalid duration \"{value}\", expected an integer followed by \
+                     `ns`, `us`, `ms`, `s`, `m` or `h` (e.g. \"50ms\")"
+                ),
+            ))
+        }
+    };
+
+    amount
+        .parse::<u64>()
+        .ok()
+        .and_then(|amount| amount.checked_mul(multiplier))
+        .ok_or_else(|| syn::Error::new_spanned(lit, format!("invalid duration \"{value}\"")))
+}
+
+fn duration_tokens(nanos: u64) -> TokenStream2 {
+    quote! { ::core::time::Duration::from_nanos(#nanos) }
+}
+
+fn parse_priority(value: &syn::LitStr) -> syn::Result<Ident> {
+    let variant = match value.value().as_str() {
+        "high" => "High",
+        "normal" => "Normal",
+        "low" => "Low",
+        other => {
+            return Err(syn::Error::new_spanned(
+                value,
+                format!("unknown priority \"{other}\", expected \"high\", \"normal\" or \"low\""),
+            ))
+        }
+    };
+    Ok(Ident::new(variant, value.span()))
+}
+
+fn check_catch_unwind_strategy(value: &syn::LitBool, strategy: Strategy) -> syn::Result<()> {
+    if value.value && matches!(strategy, Strategy::SpawnBlocking) {
+        return Err(syn::Error::new_spanned(
+            value,
+            "`catch_unwind` only applies to strategy \"block_in_place\"; \
+             panics under \"spawn_blocking\" are already reported as `TaskFailed`",
+        ));
+    }
+    Ok(())
+}
+
+/// Per-method settings after merging `#[async_wrap(...)]` with the impl-level defaults.
+struct MethodOptions {
+    strategy: Strategy,
+    catch_unwind: bool,
+    assert_unwind_safe: bool,
+    retry: Option<RetryArgs>,
+    slow_call: Option<SlowCallOptions>,
+    subscribe: Option<SubscribeOptions>,
+    coalesce: bool,
+    cache: Option<CacheArgs>,
+    batch: Option<BatchArgs>,
+    /// The default priority of a method that goes through the `dispatcher`.
+    priority: Option<Ident>,
+}
+
+fn resolve_method_options(
+    attrs: &[syn::Attribute],
+    sig: &syn::Signature,
+    args: &BlockingImplArgs,
+) -> syn::Result<MethodOptions> {
+    let method_args = parse_method_args(attrs)?;
+    if let Some(value) = &method_args.strategy {
+        return Err(syn::Error::new_spanned(
+            value,
+            "the strategy of a method is set on `#[blocking_impl]`",
+        ));
+    }
+    resolve_options(method_args, sig, args)
+}
+
+/// Parses the options of the `#[async_wrap(...)]` attribute among `attrs`, if any.
+fn parse_method_args(attrs: &[syn::Attribute]) -> syn::Result<MethodArgs> {
+    let mut method_args = MethodArgs::default();
+    for attr in attrs {
+        if attr.path().is_ident("async_wrap") && matches!(attr.meta, syn::Meta::List(_)) {
+            method_args = attr.parse_args()?;
+        }
+    }
+    Ok(method_args)
+}
+
+fn resolve_options(
+    method_args: MethodArgs,
+    sig: &syn::Signature,
+    args: &BlockingImplArgs,
+) -> syn::Result<MethodOptions> {
+    let strategy = match &method_args.strategy {
+        Some(value) => parse_strategy(value)?,
+        None => args.strategy,
+    };
+
+    if let Some(value) = &method_args.catch_unwind {
+        check_catch_unwind_strategy(value, strategy)?;
+    }
+
+    if let Some(keyword) = &method_args.coalesce {
+        if matches!(strategy, Strategy::BlockInPlace) {
+            return Err(syn::Error::new_spanned(
+                keyword,
+                "`coalesce` requires strategy \"spawn_blocking\"",
+            ));
+        }
+        if method_args.subscribe.is_some() {
+            return Err(syn::Error::new_spanned(
+                keyword,
+                "`coalesce` cannot be combined with `subscribe`",
+            ));
+        }
+    }
+
+    if let Some(batch) = &method_args.batch {
+        check_batch(batch, &method_args, strategy, sig)?;
+    }
+    let priority = resolve_priority(&method_args, args)?;
+
+    if let Some(cache) = &method_args.cache {
+        if method_args.subscribe.is_some() {
+            return Err(syn::Error::new_spanned(
+                &cache.keyword,
+                "`cache` cannot be combined with `subscribe`",
+            ));
+        }
+    }
+
+    if let Some(retry) = &method_args.retry {
+        let returns_result = match &sig.output {
+            ReturnType::Default => false,
+            ReturnType::Type(_, ty) => is_result_type(ty),
+        };
+        if !returns_result {
+            return Err(syn::Error::new_spanned(
+                &retry.keyword,
+                "`retry` requires a method returning `Result`",
+            ));
+        }
+    }
+
+    let slow_call = resolve_slow_call(method_args.slow_call, &args.slow_call)?;
+
+    let subscribe = match method_args.subscribe {
+        Some(subscribe) => {
+            if let Some(retry) = &method_args.retry {
+                return Err(syn::Error::new_spanned(
+                    &retry.keyword,
+                    "`retry` cannot be combined with `subscribe`",
+                ));
+            }
+            Some(resolve_subscribe(subscribe, sig)?)
+        }
+        None => None,
+    };
+
+    Ok(MethodOptions {
+        strategy,
+        catch_unwind: method_args
+            .catch_unwind
+            .map_or(args.catch_unwind, |value| value.value),
+        assert_unwind_safe: method_args
+            .assert_unwind_safe
+            .unwrap_or(args.assert_unwind_safe),
+        retry: method_args.retry,
+        slow_call,
+        subscribe,
+        coalesce: method_args.coalesce.is_some(),
+        cache: method_args.cache,
+        batch: method_args.batch,
+        priority,
+    })
+}
+
+/// The default priority of a method that goes through the `dispatcher`, if there is one.
+///
+/// `coalesce` and `batch` methods share blocking calls between callers, so they bypass it.
+fn resolve_priority(
+    method_args: &MethodArgs,
+    args: &BlockingImplArgs,
+) -> syn::Result<Option<Ident>> {
+    let shares_calls = method_args.coalesce.is_some() || method_args.batch.is_some();
+    match &method_args.priority {
+        Some(value) if args.dispatcher.is_none() => Err(syn::Error::new_spanned(
+            value,
+            "`priority` requires `dispatcher = \"field\"` on `#[blocking_impl]`",
+        )),
+        Some(value) if shares_calls => Err(syn::Error::new_spanned(
+            value,
+            "`priority` cannot be combined with `coalesce` or `batch`",
+        )),
+        Some(value) => parse_priority(value).map(Some),
+        None if args.dispatcher.is_some() && !shares_calls => {
+            Ok(Some(Ident::new("Normal", proc_macro2::Span::call_site())))
+        }
+        None => Ok(None),
+    }
+}
+
+fn check_batch(
+    batch: &BatchArgs,
+    method_args: &MethodArgs,
+    strategy: Strategy,
+    sig: &syn::Signature,
+) -> syn::Result<()> {
+    let keyword = &batch.keyword;
+    if matches!(strategy, Strategy::BlockInPlace) {
+        return Err(syn::Error::new_spanned(
+            keyword,
+            "`batch` requires strategy \"spawn_blocking\"",
+        ));
+    }
+    if method_args.coalesce.is_some() || method_args.subscribe.is_some() {
+        return Err(syn::Error::new_spanned(
+            keyword,
+            "`batch` cannot be combined with `coalesce` or `subscribe`",
+        ));
+    }
+    let arg_count = sig
+        .inputs
+        .iter()
+        .filter(|arg| matches!(arg, FnArg::Typed(_)))
+        .count();
+    if arg_count != 1 {
+        return Err(syn::Error::new_spanned(
+            keyword,
+            "`batch` requires a method taking exactly one argument besides `&self`",
+        ));
+    }
+    Ok(())
+}
+
+/// A `subscribe` method's callback argument, and what the async wrapper passes in its place.
+struct SubscribeOptions {
+    args: SubscribeArgs,
+    callback: Ident,
+    /// Wraps the closure for `Box<dyn Fn(..)>` and `Arc<dyn Fn(..)>` arguments.
+    container: Option<TokenStream2>,
+    /// The callback's argument type, as declared.
+    event_param: Type,
+    /// The stream's item type: `event_param`, or what it points to.
+    event: Type,
+    /// Whether the blocking method's return value is passed to `unsubscribe`.
+    has_token: bool,
+}
+
+fn resolve_subscribe(args: SubscribeArgs, sig: &syn::Signature) -> syn::Result<SubscribeOptions> {
+    let mut callbacks = sig.inputs.iter().filter_map(|arg| match arg {
+        FnArg::Typed(pat_type) => match &*pat_type.pat {
+            Pat::Ident(pat_ident) => callback_signature(&pat_type.ty)
+                .map(|callback| (&pat_ident.ident, &pat_type.ty, callback)),
+            _ => None,
+        },
+        FnArg::Receiver(_) => None,
+    });
+
+    let Some((callback, _, (container, inputs))) = callbacks.next() else {
+        return Err(syn::Error::new_spanned(
+            &args.keyword,
+            "`subscribe` requires an argument taking a callback, \
+             such as `Box<dyn Fn(Event) + Send>`",
+        ));
+    };
+    if let Some((_, ty, _)) = callbacks.next() {
+        return Err(syn::Error::new_spanned(
+            ty,
+            "`subscribe` supports a single callback argument",
+        ));
+    }
+    let Some(event_param) = inputs.first().filter(|_| inputs.len() == 1) else {
+        return Err(syn::Error::new_spanned(
+            inputs,
+            "a `subscribe` callback must take exactly one event",
+        ));
+    };
+
+    let event = match event_param {
+        Type::Reference(reference) => (*reference.elem).clone(),
+        ty => ty.clone(),
+    };
+    let has_token = match &sig.output {
+        ReturnType::Default => false,
+        ReturnType::Type(_, ty) => {
+            !is_unit_type(ty) && !result_ok_type(ty).is_some_and(is_unit_type)
+        }
+    };
+
+    Ok(SubscribeOptions {
+        callback: callback.clone(),
+        container,
+        event_param: event_param.clone(),
+        event,
+        has_token,
+        args,
+    })
+}
+
+/// The container and `Fn` arguments of a callback type: `Box<dyn Fn(..)>`, `Arc<dyn Fn(..)>`
+/// or `impl Fn(..)`, where `FnMut` is accepted in place of `Fn`.
+fn callback_signature(
+    ty: &Type,
+) -> Option<(
+    Option<TokenStream2>,
+    &syn::punctuated::Punctuated<Type, Token![,]>,
+)> {
+    let (container, bounds) = match ty {
+        Type::ImplTrait(impl_trait) => (None, &impl_trait.bounds),
+        Type::Path(type_path) => {
+            let segment = type_path.path.segments.last()?;
+            let container = if segment.ident == "Box" {
+                quote! { ::std::boxed::Box::new }
+            } else if segment.ident == "Arc" {
+                quote! { ::std::sync::Arc::new }
+            } else {
+                return None;
+            };
+            let syn::PathArguments::AngleBracketed(generics) = &segment.arguments else {
+                return None;
+            };
+            match generics.args.first()? {
+                syn::GenericArgument::Type(Type::TraitObject(object)) => {
+                    (Some(container), &object.bounds)
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    bounds.iter().find_map(|bound| {
+        let syn::TypeParamBound::Trait(bound) = bound else {
+            return None;
+        };
+        let segment = bound.path.segments.last()?;
+        if segment.ident != "Fn" && segment.ident != "FnMut" {
+            return None;
+        }
+        match &segment.arguments {
+            syn::PathArguments::Parenthesized(args) => Some((container.clone(), &args.inputs)),
+            _ => None,
+        }
+    })
+}
+
+fn is_unit_type(ty: &Type) -> bool {
+    matches!(ty, Type::Tuple(tuple) if tuple.elems.is_empty())
+}
+
+/// The first generic argument of a `Result` type.
+fn result_ok_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(generics) => match generics.args.first()? {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn resolve_slow_call(
+    method: SlowCallArgs,
+    defaults: &SlowCallArgs,
+) -> syn::Result<Option<SlowCallOptions>> {
+    let Some(threshold_nanos) = method.warn_after_nanos.or(defaults.warn_after_nanos) else {
+        if let Some(path) = &method.on_slow {
+            return Err(syn::Error::new_spanned(
+                path,
+                "`on_slow` requires `warn_after`",
+            ));
+        }
+        if let Some(value) = &method.panic_on_slow {
+            return Err(syn::Error::new_spanned(
+                value,
+                "`panic_on_slow` requires `warn_after`",
+            ));
+        }
+        return Ok(None);
+    };
+
+    Ok(Some(SlowCallOptions {
+        threshold_nanos,
+        on_slow: method.on_slow.or_else(|| defaults.on_slow.clone()),
+        panic_on_slow: method
+            .panic_on_slow
+            .or_else(|| defaults.panic_on_slow.clone())
+            .is_some_and(|value| value.value),
+    }))
+}
+
+/// What a generated async wrapper calls.
+enum CallKind {
+    /// A `&self` method of the value in the wrapper struct's field, called as `<self_ty>::name`
+    /// when the type is known. Arguments named in `by_ref` are passed by reference.
+    Method {
+        self_ty: Option<Type>,
+        by_ref: Vec<Ident>,
+    },
+    /// A free function at `path`, wrapped by a function named `async_name`.
+    Function {
+        path: TokenStream2,
+        async_name: Ident,
+    },
+}
+
+struct MethodInfo {
+    kind: CallKind,
+    name: Ident,
+    visibility: Visibility,
+    args: Vec<(Ident, Type)>,
+    return_type: Option<Type>,
+    is_result: bool,
+    doc_attrs: Vec<syn::Attribute>,
+    /// The `asyncwrap::Progress` argument, which the async wrapper creates instead of taking.
+    progress: Option<ProgressArg>,
+}
+
+struct ProgressArg {
+    name: Ident,
+    /// Whether the argument is a `&Progress`.
+    by_ref: bool,
+}
+
+fn has_async_wrap_attr(method: &ImplItemFn) -> bool {
+    method
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("async_wrap"))
+}
+
+fn remove_async_wrap_attr(method: &mut ImplItemFn) {
+    method
+        .attrs
+        .retain(|attr| !attr.path().is_ident("async_wrap"));
+}
+
+fn is_self_by_ref(arg: &FnArg) -> bool {
+    matches!(arg, FnArg::Receiver(r) if r.reference.is_some() && r.mutability.is_none())
+}
+
+fn validate_async_wrap_method(sig: &syn::Signature) -> syn::Result<()> {
+    if sig.asyncness.is_some() {
+        return Err(syn::Error::new_spanned(
+            sig.asyncness,
+            "#[async_wrap] cannot be used on async methods",
+        ));
+    }
+
+    match sig.inputs.first() {
+        Some(arg) if is_self_by_ref(arg) => Ok(()),
+        Some(FnArg::Receiver(r)) if r.mutability.is_some() => Err(syn::Error::new_spanned(
+            r,
+            "#[async_wrap] requires `&self`, not `&mut self`",
+        )),
+        Some(FnArg::Receiver(r)) if r.reference.is_none() => Err(syn::Error::new_spanned(
+            r,
+            "#[async_wrap] requires `&self`, not `self`",
+        )),
+        Some(arg) => Err(syn::Error::new_spanned(
+            arg,
+            "#[async_wrap] requires methods taking `&self`",
+        )),
+        None => Err(syn::Error::new_spanned(
+            sig,
+            "#[async_wrap] requires methods taking `&self`",
+        )),
+    }
+}
+
+fn is_result_type(ty: &Type) -> bool {
+    if let Type::Path(type_path) = ty {
+        if let Some(segment) = type_path.path.segments.last() {
+            return segment.ident == "Result";
+        }
+    }
+    false
+}
+
+fn extract_method_info(method: &ImplItemFn) -> Option<MethodInfo> {
+    let first_arg = method.sig.inputs.first()?;
+    if !is_self_by_ref(first_arg) {
+        return None;
+    }
+
+    Some(signature_info(
+        CallKind::Method {
+            self_ty: None,
+            by_ref: Vec::new(),
+        },
+        &method.sig,
+        method.vis.clone(),
+        &method.attrs,
+    ))
+}
+
+fn signature_info(
+    kind: CallKind,
+    sig: &syn::Signature,
+    visibility: Visibility,
+    attrs: &[syn::Attribute],
+) -> MethodInfo {
+    let name = sig.ident.clone();
+
+    let args: Vec<(Ident, Type)> = sig
+        .inputs
+        .iter()
+        .filter_map(|arg| {
+            if let FnArg::Typed(pat_type) = arg {
+                if let Pat::Ident(pat_ident) = &*pat_type.pat {
+                    return Some((pat_ident.ident.clone(), (*pat_type.ty).clone()));
+                }
+            }
+            None
+        })
+        .collect();
+
+    let progress = args.iter().find_map(|(name, ty)| {
+        let (ty, by_ref) = match ty {
+            Type::Reference(reference) if reference.mutability.is_none() => {
+                (&*reference.elem, true)
+            }
+            ty => (ty, false),
+        };
+        is_progress_type(ty).then(|| ProgressArg {
+            name: name.clone(),
+            by_ref,
+        })
+    });
+
+    let (return_type, is_result) = match &sig.output {
+        ReturnType::Default => (None, false),
+        ReturnType::Type(_, ty) => (Some((**ty).clone()), is_result_type(ty)),
+    };
+
+    let doc_attrs: Vec<_> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .cloned()
+        .collect();
+
+    MethodInfo {
+        kind,
+        name,
+        visibility,
+        args,
+        return_type,
+        is_result,
+        doc_attrs,
+        progress,
+    }
+}
+
+/// Whether `ty` is written `asyncwrap::Progress`. A bare `Progress` could be any type of that
+/// name, which must stay an argument.
+fn is_progress_type(ty: &Type) -> bool {
+    let Type::Path(type_path) = ty else {
+        return false;
+    };
+    let mut segments = type_path.path.segments.iter().map(|segment| &segment.ident);
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some(krate), Some(name), None) => krate == "asyncwrap" && name == "Progress",
+        _ => false,
+    }
+}
+
+/// The name used to identify the async wrapper type in `CallSite`s, without generic arguments.
+fn type_display_name(ty: &Type) -> String {
+    if let Type::Path(type_path) = ty {
+        if let Some(segment) = type_path.path.segments.last() {
+            return segment.ident.to_string();
+        }
+    }
+    quote!(#ty).to_string()
+}
+
+/// With `fault_injection = true`, fails or delays the `attempt` as the faults injected through
+/// `asyncwrap::testing` say. The check is a no-op unless `asyncwrap` has the `testing` feature.
+/// Plain `block_in_place` methods have no failure to report.
+fn inject_faults(
+    attempt: TokenStream2,
+    args: &BlockingImplArgs,
+    wraps_errors: bool,
+) -> TokenStream2 {
+    if !args.fault_injection || !wraps_errors {
+        return attempt;
+    }
+    // A `pool` runtime has no timers, so only a `handle` runtime times the delays.
+    let runtime = if let Some(handle) = &args.handle {
+        quote! { ::core::option::Option::Some(&self.#handle) }
+    } else {
+        quote! { ::core::option::Option::None }
+    };
+    quote! {
+        match ::asyncwrap::__private::injected_failure(__ASYNCWRAP_CALL_SITE, #runtime).await {
+            ::core::option::Option::Some(__asyncwrap_failure) => {
+                ::core::result::Result::Err(__asyncwrap_failure)
+            }
+            ::core::option::Option::None => { #attempt }
+        }
+    }
+}
+
+/// Plain `block_in_place` preserves the return type exactly; everything else reports panics,
+/// cancellation and rejected calls through `AsyncWrapError`.
+fn wraps_errors(options: &MethodOptions, args: &BlockingImplArgs) -> bool {
+    !matches!(options.strategy, Strategy::BlockInPlace)
+        || options.catch_unwind
+        || args.circuit_breaker.is_some()
+}
+
+fn generate_async_method(
+    info: &MethodInfo,
+    options: &MethodOptions,
+    args: &BlockingImplArgs,
+) -> TokenStream2 {
+    let name = &info.name;
+    // A `subscribe` method's callback and the progress handle are created by the wrapper,
+    // not passed in.
+    let (arg_names, arg_types): (Vec<_>, Vec<_>) = info
+        .args
+        .iter()
+        .filter(|(name, _)| {
+            options
+                .subscribe
+                .as_ref()
+                .is_none_or(|subscribe| subscribe.callback != *name)
+                && info
+                    .progress
+                    .as_ref()
+                    .is_none_or(|progress| progress.name != *name)
+        })
+        .map(|(name, ty)| (name, ty))
+        .unzip();
+
+    let wraps_errors = wraps_errors(options, args);
+    let attempt = inject_faults(
+        generate_attempt(info, options, args, wraps_errors),
+        args,
+        wraps_errors,
+    );
+    let mut outcome = match &options.retry {
+        Some(retry) => generate_retry_loop(retry, info, args, &attempt, wraps_errors),
+        None => attempt,
+    };
+    if let Some(breaker) = &args.circuit_breaker {
+        outcome = guard_with_circuit_breaker(breaker, info, &outcome);
+    }
+    let invalidate = options.cache.as_ref().map(|cache| {
+        outcome = cache_outcome(cache, info, args, wraps_errors, &outcome);
+        generate_invalidate_cache(info, args)
+    });
+
+    let (mut return_type, mut body) = map_return_type(info, wraps_errors, outcome);
+
+    let mut channel = None;
+    if let Some(subscribe) = &options.subscribe {
+        (return_type, body) =
+            return_subscription(subscribe, info, options.strategy, args, wraps_errors, &body);
+        channel = Some(generate_subscription_channel(subscribe));
+    }
+
+    let needs_call_site = wraps_errors || args.observer.is_some() || options.slow_call.is_some();
+    let call_site = needs_call_site.then(|| {
+        let type_name = match &info.kind {
+            CallKind::Method { .. } => {
+                let type_name = type_display_name(&args.async_type);
+                quote! { #type_name }
+            }
+            CallKind::Function { .. } => quote! { ::core::module_path!() },
+        };
+        let method_name = name.to_string();
+        quote! {
+            const __ASYNCWRAP_CALL_SITE: ::asyncwrap::CallSite =
+                ::asyncwrap::CallSite::new(#type_name, #method_name);
+        }
+    });
+
+    let prelude = quote! { #call_site #channel };
+    let send_checks = send_checks(info, options, args);
+    let where_clause = (!send_checks.is_empty()).then(|| {
+        let predicates = send_checks.iter().map(|check| {
+            let ty = &check.ty;
+            // Spanning the trait at the type keeps the whole predicate, where the error is
+            // reported, in the user's code.
+            let name = Ident::new(&check.name.to_string(), ty.span());
+            quote_spanned! {ty.span()=> #ty: #name }
+        });
+        quote! { where #(#predicates),* }
+    });
+    let entry_points = generate_entry_points(
+        info,
+        options,
+        (&arg_names, &arg_types),
+        (return_type, where_clause.as_ref()),
+        &prelude,
+        &body,
+    );
+    quote! {
+        #entry_points
+        #invalidate
+    }
+}
+
+/// The async wrapper running `prelude` and `body`. With a `dispatcher`, this is the
+/// `<method>_with_priority` variant, and the wrapper calls it with the method's priority.
+fn generate_entry_points(
+    info: &MethodInfo,
+    options: &MethodOptions,
+    (arg_names, arg_types): (&[&Ident], &[&Type]),
+    (return_type, where_clause): (Option<TokenStream2>, Option<&TokenStream2>),
+    prelude: &TokenStream2,
+    body: &TokenStream2,
+) -> TokenStream2 {
+    let name = &info.name;
+    let vis = &info.visibility;
+    let doc_attrs = &info.doc_attrs;
+    let params = quote! { #(#arg_names: #arg_types),* };
+
+    let (wrapper_name, wrapper_docs, priority) = if options.priority.is_some() {
+        let param = priority_param(info);
+        let doc = format!("Like [`Self::{name}`], with the dispatcher priority of this call.");
+        (
+            quote::format_ident!("{}_with_priority", name),
+            quote! { #[doc = #doc] },
+            Some(quote! { #param: ::asyncwrap::Priority, }),
+        )
+    } else {
+        (name.clone(), quote! { #(#doc_attrs)* }, None)
+    };
+    let signature = match &info.kind {
+        CallKind::Method { .. } => quote! { #wrapper_name(&self, #priority #params) },
+        CallKind::Function { async_name, .. } => quote! { #async_name(#params) },
+    };
+
+    let (returns, wrapper) = if let Some(ProgressArg { name: progress, .. }) = &info.progress {
+        let output = return_type.unwrap_or_else(|| quote! { () });
+        let captures = matches!(info.kind, CallKind::Method { .. }).then(|| quote! { + '_ });
+        let returns = quote! {
+            -> ::asyncwrap::WithProgress<impl ::core::future::Future<Output = #output> #captures>
+        };
+        let wrapper = quote! {
+            #wrapper_docs
+            #vis fn #signature #returns #where_clause {
+                let (#progress, __asyncwrap_progress) = ::asyncwrap::Progress::channel();
+                ::asyncwrap::WithProgress::new(__asyncwrap_progress, async move {
+                    #prelude
+                    let __asyncwrap_output: #output = { #body };
+                    __asyncwrap_output
+                })
+            }
+        };
+        (returns, wrapper)
+    } else {
+        let returns = return_type.map(|ty| quote! { -> #ty });
+        let wrapper = quote! {
+            #wrapper_docs
+            #[must_use = "futures do nothing unless you `.await` or poll them"]
+            #vis async fn #signature #returns #where_clause {
+                #prelude
+                #body
+            }
+        };
+        (quote! { #returns }, wrapper)
+    };
+
+    let Some(priority) = &options.priority else {
+        return wrapper;
+    };
+    let call = quote! { self.#wrapper_name(::asyncwrap::Priority::#priority, #(#arg_names),*) };
+    let delegate = if info.progress.is_some() {
+        quote! {
+            #(#doc_attrs)*
+            #vis fn #name(&self, #params) #returns #where_clause {
+                #call
+            }
+        }
+    } else {
+        quote! {
+            #(#doc_attrs)*
+            #[must_use = "futures do nothing unless you `.await` or poll them"]
+            #vis async fn #name(&self, #params) #returns #where_clause {
+                #call.await
+            }
+        }
+    };
+    quote! {
+        #delegate
+        #wrapper
+    }
+}
+
+/// The return type and value of the async wrapper, given the `outcome` of the blocking call.
+fn map_return_type(
+    info: &MethodInfo,
+    wraps_errors: bool,
+    outcome: TokenStream2,
+) -> (Option<TokenStream2>, TokenStream2) {
+    if !wraps_errors {
+        (info.return_type.as_ref().map(|ty| quote! { #ty }), outcome)
+    } else if info.is_result {
+        let inner_return = info.return_type.as_ref().unwrap();
+        (
+            Some(quote! { ::asyncwrap::AsyncWrapResult<#inner_return> }),
+            quote! {
+                { #outcome }
+                    .map_err(::asyncwrap::AsyncWrapError::TaskFailed)?
+                    .map_err(::asyncwrap::AsyncWrapError::Inner)
+            },
+        )
+    } else {
+        let ret_ty = info
+            .return_type
+            .as_ref()
+            .map_or_else(|| quote! { () }, |ty| quote! { #ty });
+        (
+            Some(quote! {
+                ::core::result::Result<
+                    #ret_ty,
+                    ::asyncwrap::AsyncWrapError<::core::convert::Infallible>,
+                >
+            }),
+            quote! { { #outcome }.map_err(::asyncwrap::AsyncWrapError::TaskFailed) },
+        )
+    }
+}
+
+/// Replaces the return type and value of a `subscribe` method's wrapper with a `Subscription`,
+/// keeping any error type.
+fn return_subscription(
+    subscribe: &SubscribeOptions,
+    info: &MethodInfo,
+    strategy: Strategy,
+    args: &BlockingImplArgs,
+    wraps_errors: bool,
+    body: &TokenStream2,
+) -> (Option<TokenStream2>, TokenStream2) {
+    let error_type = match (&info.return_type, wraps_errors) {
+        (Some(ty), true) if info.is_result => Some(quote! {
+            ::asyncwrap::AsyncWrapError<<#ty as ::asyncwrap::ResultType>::Err>
+        }),
+        (_, true) => Some(quote! { ::asyncwrap::AsyncWrapError<::core::convert::Infallible> }),
+        (Some(ty), false) if info.is_result => {
+            Some(quote! { <#ty as ::asyncwrap::ResultType>::Err })
+        }
+        _ => None,
+    };
+    let (subscription_type, subscription) = generate_subscription(subscribe, info, strategy, args);
+
+    match error_type {
+        Some(error_type) => (
+            Some(quote! { ::core::result::Result<#subscription_type, #error_type> }),
+            quote! {
+                let __asyncwrap_result = { #body };
+                __asyncwrap_result.map(|__asyncwrap_token| #subscription)
+            },
+        ),
+        None => (
+            Some(quote! { #subscription_type }),
+            quote! {
+                let __asyncwrap_token = { #body };
+                #subscription
+            },
+        ),
+    }
+}
+
+/// Creates the event channel and the callback passed to a `subscribe` method.
+fn generate_subscription_channel(subscribe: &SubscribeOptions) -> TokenStream2 {
+    let callback = &subscribe.callback;
+    let event = &subscribe.event;
+    let event_param = &subscribe.event_param;
+    let capacity = subscribe.args.capacity;
+    let overflow = &subscribe.args.overflow;
+    let sent = if matches!(event_param, Type::Reference(_)) {
+        quote! { ::core::clone::Clone::clone(__asyncwrap_event) }
+    } else {
+        quote! { __asyncwrap_event }
+    };
+    let closure = quote! {
+        move |__asyncwrap_event: #event_param| __asyncwrap_sender.send(#sent)
+    };
+    let callback_value = match &subscribe.container {
+        Some(container) => quote! { #container(#closure) },
+        None => closure,
+    };
+
+    quote! {
+        let (__asyncwrap_sender, __asyncwrap_events) =
+            ::asyncwrap::__private::subscription_channel::<#event>(
+                #capacity,
+                ::asyncwrap::Overflow::#overflow,
+            );
+        let #callback = #callback_value;
+    }
+}
+
+/// The `Subscription` type returned by a `subscribe` method, and the expression building it
+/// from `__asyncwrap_token`, the blocking method's return value.
+fn generate_subscription(
+    subscribe: &SubscribeOptions,
+    info: &MethodInfo,
+    strategy: Strategy,
+    args: &BlockingImplArgs,
+) -> (TokenStream2, TokenStream2) {
+    let event = &subscribe.event;
+    let Some(unsubscribe) = &subscribe.args.unsubscribe else {
+        return (
+            quote! { ::asyncwrap::Subscription<'static, #event> },
+            quote! { ::asyncwrap::Subscription::without_unsubscribe(__asyncwrap_events) },
+        );
+    };
+
+    let field = &args.field;
+    let receiver = match strategy {
+        Strategy::SpawnBlocking => quote! { __asyncwrap_inner },
+        Strategy::BlockInPlace => quote! { self.#field },
+    };
+    let token = subscribe.has_token.then(|| quote! { __asyncwrap_token });
+    let call = if let CallKind::Method {
+        self_ty: Some(self_ty),
+        ..
+    } = &info.kind
+    {
+        quote! { <#self_ty>::#unsubscribe(&#receiver, #token) }
+    } else {
+        quote! { #receiver.#unsubscribe(#token) }
+    };
+
+    match strategy {
+        Strategy::SpawnBlocking => (
+            quote! { ::asyncwrap::Subscription<'static, #event> },
+            quote! {{
+                let __asyncwrap_inner = ::std::sync::Arc::clone(__asyncwrap_shared(self));
+                ::asyncwrap::Subscription::detached(__asyncwrap_events, move || {
+                    let _ = #call;
+                })
+            }},
+        ),
+        Strategy::BlockInPlace => (
+            quote! { ::asyncwrap::Subscription<'_, #event> },
+            quote! {
+                ::asyncwrap::Subscription::new(__asyncwrap_events, move || {
+                    ::tokio::task::block_in_place(|| {
+                        let _ = #call;
+                    });
+                })
+            },
+        ),
+    }
+}
+
+/// Generates a single call of the blocking method.
+///
+/// When `wraps_errors` is set this evaluates to `Result<R, TaskFailure>`, otherwise to `R`,
+/// where `R` is the blocking method's return type.
+fn generate_attempt(
+    info: &MethodInfo,
+    options: &MethodOptions,
+    args: &BlockingImplArgs,
+    wraps_errors: bool,
+) -> TokenStream2 {
+    let (mut call, clone_inner) = blocking_call(info, options.strategy, args);
+
+    if let Some(slow_call) = &options.slow_call {
+        call = time_slow_call(slow_call, &call);
+    }
+
+    let observation = args.observer.as_ref().map(|observer| {
+        let is_ok = if info.is_result {
+            quote! { ::core::result::Result::is_ok }
+        } else {
+            quote! { |_: &_| true }
+        };
+        call = quote! { __asyncwrap_observation.run(move || #call, #is_ok) };
+        quote! {
+            let __asyncwrap_observation =
+                ::asyncwrap::__private::CallObservation::start(&#observer, __ASYNCWRAP_CALL_SITE);
+        }
+    });
+    if let Some(thread_init) = thread_init_call(args) {
+        call = quote! {{ #thread_init; #call }};
+    }
+    // The permit is moved into the blocking closure, so it is held until the call returns even
+    // if the caller stops waiting.
+    let permit = dispatch_permit(info, options, args);
+    if permit.is_some() && matches!(options.strategy, Strategy::SpawnBlocking) {
+        call = quote! {{ let __asyncwrap_permit = __asyncwrap_permit; #call }};
+    }
+    // The observation starts once the permit is acquired, so a caller cancelled while queued
+    // isn't reported as started.
+    let admission = quote! { #permit #observation };
+    let report_failure = observation
+        .as_ref()
+        .map(|_| quote! { __asyncwrap_observation.report_failure(&__asyncwrap_failure); });
+
+    match options.strategy {
+        Strategy::SpawnBlocking if options.batch.is_some() => {
+            let batch = options.batch.as_ref().unwrap();
+            batch_call(batch, info, options, args, clone_inner.as_ref())
+        }
+        Strategy::SpawnBlocking if options.coalesce => coalesce_call(
+            info,
+            args,
+            clone_inner.as_ref(),
+            observation.as_ref(),
+            &call,
+        ),
+        Strategy::SpawnBlocking => {
+            let spawn_blocking = if args.pool.is_some() || args.handle.is_some() {
+                let runtime = blocking_runtime(args);
+                quote! { #runtime.spawn_blocking }
+            } else {
+                quote! { ::tokio::task::spawn_blocking }
+            };
+            let (send, call, receive) = send_checked(info, options, args, &call);
+            quote! {
+                #clone_inner
+                #admission
+                #send
+                #spawn_blocking(move || #call)
+                    .await
+                    #receive
+                    .map_err(|__asyncwrap_err| {
+                        let __asyncwrap_failure = ::asyncwrap::TaskFailure::from_join_error(
+                            __asyncwrap_err,
+                            __ASYNCWRAP_CALL_SITE,
+                        );
+                        #report_failure
+                        __asyncwrap_failure
+                    })
+            }
+        }
+        Strategy::BlockInPlace if options.catch_unwind => {
+            let guarded_call = if options.assert_unwind_safe {
+                quote! { ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| #call)) }
+            } else {
+                quote! { ::std::panic::catch_unwind(|| #call) }
+            };
+            quote! {
+                #admission
+                ::tokio::task::block_in_place(|| #guarded_call).map_err(|__asyncwrap_payload| {
+                    ::asyncwrap::TaskFailure::from_panic(__asyncwrap_payload, __ASYNCWRAP_CALL_SITE)
+                })
+            }
+        }
+        Strategy::BlockInPlace if wraps_errors => quote! {
+            #admission
+            ::core::result::Result::<_, ::asyncwrap::TaskFailure>::Ok(
+                ::tokio::task::block_in_place(|| #call)
+            )
+        },
+        Strategy::BlockInPlace => quote! {
+            #admission
+            ::tokio::task::block_in_place(|| #call)
+        },
+    }
+}
+
+/// The call of the blocking method, and for `spawn_blocking` methods the statement cloning the
+/// `Arc` it is called on.
+fn blocking_call(
+    info: &MethodInfo,
+    strategy: Strategy,
+    args: &BlockingImplArgs,
+) -> (TokenStream2, Option<TokenStream2>) {
+    let name = &info.name;
+    let field = &args.field;
+    let call_args = call_arguments(info);
+
+    match &info.kind {
+        CallKind::Function { path, .. } => (quote! { #path(#(#call_args),*) }, None),
+        CallKind::Method { self_ty, .. } => {
+            let (receiver, clone_inner) = match strategy {
+                Strategy::SpawnBlocking => (
+                    quote! { __asyncwrap_inner },
+                    Some(quote! {
+                        let __asyncwrap_inner = ::std::sync::Arc::clone(__asyncwrap_shared(self));
+                    }),
+                ),
+                Strategy::BlockInPlace => (quote! { self.#field }, None),
+            };
+            let call = if let Some(self_ty) = self_ty {
+                quote! { <#self_ty>::#name(&#receiver, #(#call_args),*) }
+            } else {
+                quote! { #receiver.#name(#(#call_args),*) }
+            };
+            (call, clone_inner)
+        }
+    }
+}
+
+/// Waits for a permit from the `dispatcher`, with the priority of the call.
+fn dispatch_permit(
+    info: &MethodInfo,
+    options: &MethodOptions,
+    args: &BlockingImplArgs,
+) -> Option<TokenStream2> {
+    options.priority.as_ref()?;
+    let dispatcher = args.dispatcher.as_ref()?;
+    let priority = priority_param(info);
+    Some(quote! {
+        let __asyncwrap_permit = self.#dispatcher.acquire(#priority).await;
+    })
+}
+
+/// The priority parameter of a `<method>_with_priority` wrapper.
+fn priority_param(info: &MethodInfo) -> Ident {
+    let name = if info.args.iter().any(|(name, _)| name == "priority") {
+        "__asyncwrap_priority"
+    } else {
+        "priority"
+    };
+    Ident::new(name, proc_macro2::Span::call_site())
+}
+
+/// Runs the `thread_init` hook, once per thread, before a blocking call.
+fn thread_init_call(args: &BlockingImplArgs) -> Option<TokenStream2> {
+    let init = args.thread_init.as_ref()?;
+    let deinit = args.thread_deinit.as_ref().map_or_else(
+        || quote! { ::core::option::Option::None },
+        |deinit| quote! { ::core::option::Option::Some(#deinit as fn()) },
+    );
+    Some(quote! { ::asyncwrap::__private::thread_init(#init, #deinit) })
+}
+
+/// The runtime whose blocking pool runs the calls of `spawn_blocking` methods: the `handle`
+/// field or the `pool`, if one is set, or the current runtime.
+fn blocking_runtime(args: &BlockingImplArgs) -> TokenStream2 {
+    if let Some(handle) = &args.handle {
+        return quote! { (&self.#handle) };
+    }
+    let Some(name) = &args.pool else {
+        return quote! { &::tokio::runtime::Handle::current() };
+    };
+    quote! {{
+        static __ASYNCWRAP_POOL: ::asyncwrap::__private::Pool =
+            ::asyncwrap::__private::Pool::new(#name);
+        __ASYNCWRAP_POOL.handle()
+    }}
+}
+
+/// The arguments passed to the blocking method, by reference where it takes them by reference.
+fn call_arguments(info: &MethodInfo) -> Vec<TokenStream2> {
+    let by_ref: &[Ident] = match &info.kind {
+        CallKind::Method { by_ref, .. } => by_ref,
+        CallKind::Function { .. } => &[],
+    };
+    info.args
+        .iter()
+        .map(|(name, _)| {
+            let progress_by_ref = info
+                .progress
+                .as_ref()
+                .is_some_and(|progress| progress.by_ref && progress.name == *name);
+            if by_ref.contains(name) || progress_by_ref {
+                quote! { &#name }
+            } else {
+                quote! { #name }
+            }
+        })
+        .collect()
+}
+
+/// Runs `call` on the blocking pool unless an identical call is in flight, in which case its
+/// result is shared.
+fn coalesce_call(
+    info: &MethodInfo,
+    args: &BlockingImplArgs,
+    clone_inner: Option<&TokenStream2>,
+    observation: Option<&TokenStream2>,
+    call: &TokenStream2,
+) -> TokenStream2 {
+    let state = &args.state;
+    let method_name = info.name.to_string();
+    let runtime = blocking_runtime(args);
+    let keys = info.args.iter().map(|(name, ty)| {
+        quote_spanned! {ty.span()=>
+            <#ty as ::asyncwrap::__private::CoalesceKey>::coalesce_key(&#name)
+        }
+    });
+    let output = info
+        .return_type
+        .as_ref()
+        .map_or_else(|| quote! { () }, |ty| quote! { #ty });
+
+    // Only the caller that starts the blocking call observes it, from before the call is
+    // queued.
+    let observed = observation.map_or_else(
+        || quote! { ::core::option::Option::None },
+        |_| quote! { ::core::option::Option::Some(__asyncwrap_observation) },
+    );
+    quote! {
+        #clone_inner
+        let __asyncwrap_key = ::std::vec![#(#keys),*];
+        self.#state
+            .slot::<::asyncwrap::__private::Flights<#output>>(#method_name)
+            .run(#runtime, __asyncwrap_key, __ASYNCWRAP_CALL_SITE, move || {
+                #observation
+                (#observed, move || #call)
+            })
+            .await
+    }
+}
+
+/// Adds the argument to the pending batch of a `batch` method, which is sent to the batch
+/// method on the blocking pool.
+///
+/// Observers and slow-call detection see each batch as one call, made by the caller whose
+/// closure runs it.
+fn batch_call(
+    batch: &BatchArgs,
+    info: &MethodInfo,
+    options: &MethodOptions,
+    args: &BlockingImplArgs,
+    clone_inner: Option<&TokenStream2>,
+) -> TokenStream2 {
+    let state = &args.state;
+    let method_name = info.name.to_string();
+    let (arg, arg_ty) = &info.args[0];
+    let output = info
+        .return_type
+        .as_ref()
+        .map_or_else(|| quote! { () }, |ty| quote! { #ty });
+    let with = &batch.with;
+    let call = if let CallKind::Method {
+        self_ty: Some(self_ty),
+        ..
+    } = &info.kind
+    {
+        quote! { <#self_ty>::#with(&__asyncwrap_inner, __asyncwrap_batch) }
+    } else {
+        quote! { __asyncwrap_inner.#with(__asyncwrap_batch) }
+    };
+    let call = match &options.slow_call {
+        Some(slow_call) => time_slow_call(slow_call, &call),
+        None => call,
+    };
+    let call = match &args.observer {
+        Some(observer) => {
+            let is_ok = if info.is_result {
+                quote! {
+                    |__asyncwrap_results: &::std::vec::Vec<_>| {
+                        __asyncwrap_results.iter().all(::core::result::Result::is_ok)
+                    }
+                }
+            } else {
+                quote! { |_: &_| true }
+            };
+            quote! {
+                ::asyncwrap::__private::CallObservation::start(&#observer, __ASYNCWRAP_CALL_SITE)
+                    .run(move || #call, #is_ok)
+            }
+        }
+        None => call,
+    };
+    let call = match thread_init_call(args) {
+        Some(thread_init) => quote! {{ #thread_init; #call }},
+        None => call,
+    };
+    let max_size = batch.max_size;
+    let max_delay = duration_tokens(batch.max_delay_nanos);
+    let runtime = blocking_runtime(args);
+
+    // The batch's timer is spawned on the `handle` runtime, which needn't be the current one.
+    let enter = args.handle.as_ref().map(|handle| {
+        quote! { let __asyncwrap_guard = self.#handle.enter(); }
+    });
+
+    quote! {
+        #clone_inner
+        let __asyncwrap_batched = {
+            #enter
+            self.#state
+                .slot::<::asyncwrap::__private::Batcher<#arg_ty, #output>>(#method_name)
+                .submit(
+                    #runtime,
+                    #arg,
+                    #max_size,
+                    #max_delay,
+                    __ASYNCWRAP_CALL_SITE,
+                    move |__asyncwrap_batch: ::std::vec::Vec<#arg_ty>| #call,
+                )
+        };
+        __asyncwrap_batched.await
+    }
+}
+
+/// The `Cache` slot of a `cache` method in the wrapper's `WrapState`.
+fn cache_slot(info: &MethodInfo, args: &BlockingImplArgs) -> TokenStream2 {
+    let state = &args.state;
+    let method_name = info.name.to_string();
+    let output = info
+        .return_type
+        .as_ref()
+        .map_or_else(|| quote! { () }, |ty| quote! { #ty });
+    quote! { self.#state.slot::<::asyncwrap::__private::Cache<#output>>(#method_name) }
+}
+
+/// Returns the cached result of an earlier call with equal arguments, or evaluates `outcome`
+/// and caches it if it succeeded.
+fn cache_outcome(
+    cache: &CacheArgs,
+    info: &MethodInfo,
+    args: &BlockingImplArgs,
+    wraps_errors: bool,
+    outcome: &TokenStream2,
+) -> TokenStream2 {
+    let slot = cache_slot(info, args);
+    let keys = info.args.iter().map(|(name, ty)| {
+        quote_spanned! {ty.span()=>
+            <#ty as ::asyncwrap::__private::CacheKey>::cache_key(&#name)
+        }
+    });
+    let ttl = cache.ttl_nanos.map_or_else(
+        || quote! { ::core::option::Option::None },
+        |nanos| {
+            let ttl = duration_tokens(nanos);
+            quote! { ::core::option::Option::Some(#ttl) }
+        },
+    );
+    let capacity = cache.capacity;
+    let (hit, value) = if wraps_errors {
+        (
+            quote! { ::core::result::Result::Ok(__asyncwrap_value) },
+            quote! { ::core::result::Result::as_ref(&__asyncwrap_outcome).ok() },
+        )
+    } else {
+        (
+            quote! { __asyncwrap_value },
+            quote! { ::core::option::Option::Some(&__asyncwrap_outcome) },
+        )
+    };
+    // Errors returned by the blocking method aren't cached either.
+    let succeeded = info
+        .is_result
+        .then(|| quote! { .filter(|__asyncwrap_value| __asyncwrap_value.is_ok()) });
+
+    quote! {
+        let __asyncwrap_cache = #slot;
+        let __asyncwrap_key = ::std::vec![#(#keys),*];
+        if let ::core::option::Option::Some(__asyncwrap_value) =
+            __asyncwrap_cache.get(&__asyncwrap_key, #ttl)
+        {
+            #hit
+        } else {
+            let __asyncwrap_outcome = { #outcome };
+            let __asyncwrap_value = #value;
+            if let ::core::option::Option::Some(__asyncwrap_value) = __asyncwrap_value #succeeded {
+                __asyncwrap_cache.insert(__asyncwrap_key, __asyncwrap_value, #ttl, #capacity);
+            }
+            __asyncwrap_outcome
+        }
+    }
+}
+
+/// The `invalidate_<method>()` method of a `cache` method.
+fn generate_invalidate_cache(info: &MethodInfo, args: &BlockingImplArgs) -> TokenStream2 {
+    let vis = &info.visibility;
+    let slot = cache_slot(info, args);
+    let invalidate = quote::format_ident!("invalidate_{}", info.name);
+    let doc = format!("Discards the results cached by [`Self::{}`].", info.name);
+    quote! {
+        #[doc = #doc]
+        #vis fn #invalidate(&self) {
+            #slot.clear();
+        }
+    }
+}
+
+/// The `clear_cache()` method of a wrapper with `cache` methods, given the slot of each.
+fn generate_clear_cache(slots: &[TokenStream2]) -> Option<TokenStream2> {
+    (!slots.is_empty()).then(|| {
+        quote! {
+            /// Discards the results cached by every method using `cache`.
+            pub fn clear_cache(&self) {
+                #(#slots.clear();)*
+            }
+        }
+    })
+}
+
+/// Wraps the blocking `call` so it is reported when it exceeds the `warn_after` threshold.
+fn time_slow_call(slow_call: &SlowCallOptions, call: &TokenStream2) -> TokenStream2 {
+    let threshold = duration_tokens(slow_call.threshold_nanos);
+    let on_slow = slow_call.on_slow.as_ref().map_or_else(
+        || quote! { ::asyncwrap::log_slow_call },
+        |path| quote! { #path },
+    );
+    let panic = if slow_call.panic_on_slow {
+        quote! { ::core::cfg!(debug_assertions) }
+    } else {
+        quote! { false }
+    };
+
+    quote! {
+        {
+            let __asyncwrap_timer =
+                ::asyncwrap::__private::SlowCallTimer::start(__ASYNCWRAP_CALL_SITE, #threshold);
+            let __asyncwrap_value = #call;
+            __asyncwrap_timer.finish(#on_slow, #panic);
+            __asyncwrap_value
+        }
+    }
+}
+
+/// Wraps `outcome` so it only runs when the circuit breaker admits the call, and records
+/// whether it succeeded.
+fn guard_with_circuit_breaker(
+    breaker: &CircuitBreakerArgs,
+    info: &MethodInfo,
+    outcome: &TokenStream2,
+) -> TokenStream2 {
+    let breaker_field = &breaker.field;
+    let succeeded = if info.is_result {
+        quote! {
+            ::core::matches!(
+                __asyncwrap_outcome,
+                ::core::result::Result::Ok(::core::result::Result::Ok(_))
+            )
+        }
+    } else {
+        quote! { __asyncwrap_outcome.is_ok() }
+    };
+
+    quote! {
+        let ::core::option::Option::Some(__asyncwrap_permit) =
+            self.#breaker_field.try_acquire(&Self::__ASYNCWRAP_CIRCUIT_BREAKER)
+        else {
+            return ::core::result::Result::Err(
+                ::asyncwrap::AsyncWrapError::CircuitOpen(__ASYNCWRAP_CALL_SITE),
+            );
+        };
+        let __asyncwrap_outcome = { #outcome };
+        __asyncwrap_permit.record(#succeeded);
+        __asyncwrap_outcome
+    }
+}
+
+/// Wraps `attempt` in a loop that re-runs it while it fails with a retryable error.
+///
+/// Arguments are cloned before every attempt through `asyncwrap::__private::RetryArg`, spanned
+/// at each argument's type so a missing `Clone` impl is reported in the user's signature.
+fn generate_retry_loop(
+    retry: &RetryArgs,
+    info: &MethodInfo,
+    args: &BlockingImplArgs,
+    attempt: &TokenStream2,
+    wraps_errors: bool,
+) -> TokenStream2 {
+    let max_attempts = retry.max_attempts;
+    let backoff = &retry.backoff;
+    let base = duration_tokens(retry.base_nanos);
+
+    let clones = info.args.iter().map(|(name, ty)| {
+        let ty = match &info.progress {
+            Some(progress) if progress.name == *name => quote! { ::asyncwrap::Progress },
+            _ => quote! { #ty },
+        };
+        quote_spanned! {ty.span()=>
+            let #name = <#ty as ::asyncwrap::__private::RetryArg>::retry_clone(&#name);
+        }
+    });
+    let failed = if wraps_errors {
+        quote! { ::core::result::Result::Ok(::core::result::Result::Err(__asyncwrap_err)) }
+    } else {
+        quote! { ::core::result::Result::Err(__asyncwrap_err) }
+    };
+    let when = retry
+        .when
+        .as_ref()
+        .map_or_else(|| quote! { true }, |when| quote! { #when(__asyncwrap_err) });
+    let backoff_sleep = args.handle.as_ref().map_or_else(
+        || quote! { __ASYNCWRAP_RETRY.backoff(__asyncwrap_attempt) },
+        |handle| quote! { __ASYNCWRAP_RETRY.backoff_on(&self.#handle, __asyncwrap_attempt) },
+    );
+
+    quote! {
+        const __ASYNCWRAP_RETRY: ::asyncwrap::RetryPolicy =
+            ::asyncwrap::RetryPolicy::new(#max_attempts, ::asyncwrap::Backoff::#backoff, #base);
+        let mut __asyncwrap_attempt: u32 = 0;
+        loop {
+            __asyncwrap_attempt += 1;
+            #(#clones)*
+            let __asyncwrap_result = { #attempt };
+            match &__asyncwrap_result {
+                #failed if __ASYNCWRAP_RETRY.should_retry(__asyncwrap_attempt) && #when => {}
+                _ => break __asyncwrap_result,
+            }
+            #backoff_sleep.await;
+        }
+    }
+}
+
+/// For strategy `spawn_blocking`, the `__asyncwrap_shared()` function through which the
+/// wrappers clone the `Arc` in the blocking field. Its bound reports a field of the wrong type
+/// once, at the attribute, instead of in every wrapper. It is a free function, emitted next to
+/// the wrappers in their `const _` block, so several impls can wrap into the same type.
+fn generate_shared_field(
+    args: &BlockingImplArgs,
+    blocking_type: &Type,
+    generics: &syn::Generics,
+) -> Option<TokenStream2> {
+    if !matches!(args.strategy, Strategy::SpawnBlocking) {
+        return None;
+    }
+    let field = &args.field;
+    let async_name = type_display_name(&args.async_type);
+    let blocking_name = type_display_name(blocking_type);
+    let message = format!(
+        "`{async_name}` must have field `{field}: Arc<{blocking_name}>` for strategy spawn_blocking"
+    );
+    let label = format!("`{field}` is a `{{Self}}`, not an `Arc<{blocking_name}>`");
+    let note = format!(
+        "blocking calls run on another thread, which shares `{blocking_name}` with the wrapper: \
+         store it as `Arc::new(value)`; a `Box` can't be shared, and an `Rc` can't be sent to \
+         another thread"
+    );
+    let block_in_place = "strategy \"block_in_place\" runs calls on the current thread, and \
+                          accepts a plain, `Box` or `Rc` field";
+    let async_type = &args.async_type;
+    let (params, _, where_clause) = generics.split_for_impl();
+
+    Some(quote! {
+        #[diagnostic::on_unimplemented(
+            message = #message,
+            label = #label,
+            note = #note,
+            note = #block_in_place,
+        )]
+        trait SharedField<T: ?::core::marker::Sized> {
+            fn shared(&self) -> &::std::sync::Arc<T>;
+        }
+
+        impl<T: ?::core::marker::Sized> SharedField<T> for ::std::sync::Arc<T> {
+            fn shared(&self) -> &::std::sync::Arc<T> {
+                self
+            }
+        }
+
+        fn __asyncwrap_shared #params (
+            wrapper: &#async_type,
+        ) -> &::std::sync::Arc<#blocking_type> #where_clause {
+            SharedField::<#blocking_type>::shared(&wrapper.#field)
+        }
+    })
+}
+
+/// The assertion that an argument or the return value of a `spawn_blocking` method is
+/// `Send + 'static`, checked by the wrapper's `where` clause so a violation is reported once, at
+/// the type in the signature.
+struct SendCheck {
+    /// The local trait whose `on_unimplemented` message names the argument.
+    name: Ident,
+    ty: Type,
+    /// `None` for the return value.
+    arg: Option<Ident>,
+}
+
+/// The `SendCheck`s of a `spawn_blocking` method: one for each argument the wrapper takes, and
+/// one for the return value. Types the `where` clause can't name, with elided lifetimes or
+/// `impl Trait`, are left to the `spawn_blocking` bound.
+fn send_checks(
+    info: &MethodInfo,
+    options: &MethodOptions,
+    args: &BlockingImplArgs,
+) -> Vec<SendCheck> {
+    let CallKind::Method { .. } = info.kind else {
+        return Vec::new();
+    };
+    if !matches!(args.strategy, Strategy::SpawnBlocking) {
+        return Vec::new();
+    }
+    let method = &info.name;
+    let passed = info.args.iter().filter(|(name, _)| {
+        options
+            .subscribe
+            .as_ref()
+            .is_none_or(|subscribe| subscribe.callback != *name)
+            && info
+                .progress
+                .as_ref()
+                .is_none_or(|progress| progress.name != *name)
+    });
+    passed
+        .map(|(name, ty)| (Some(name), ty))
+        .chain(info.return_type.iter().map(|ty| (None, ty)))
+        .filter(|(_, ty)| is_nameable_in_where_clause(&quote! { #ty }))
+        .map(|(arg, ty)| {
+            let suffix = arg.map_or_else(|| "return".to_owned(), ToString::to_string);
+            SendCheck {
+                name: quote::format_ident!("__asyncwrap_send_{}_{}", method, suffix),
+                ty: ty.clone(),
+                arg: arg.cloned(),
+            }
+        })
+        .collect()
+}
+
+/// Whether `tokens` have no elided lifetime and no `impl Trait`.
+fn is_nameable_in_where_clause(tokens: &TokenStream2) -> bool {
+    let tokens: Vec<_> = tokens.clone().into_iter().collect();
+    tokens.iter().enumerate().all(|(i, token)| match token {
+        proc_macro2::TokenTree::Punct(punct) if punct.as_char() == '&' => matches!(
+            tokens.get(i + 1),
+            Some(proc_macro2::TokenTree::Punct(next)) if next.as_char() == '\''
+        ),
+        proc_macro2::TokenTree::Punct(punct) if punct.as_char() == '\'' => !matches!(
+            tokens.get(i + 1),
+            Some(proc_macro2::TokenTree::Ident(next)) if next == "_"
+        ),
+        proc_macro2::TokenTree::Ident(ident) => ident != "impl",
+        proc_macro2::TokenTree::Group(group) => is_nameable_in_where_clause(&group.stream()),
+        proc_macro2::TokenTree::Punct(_) | proc_macro2::TokenTree::Literal(_) => true,
+    })
+}
+
+/// The local traits of a method's `SendCheck`s, emitted next to the impl of the wrapper.
+fn generate_send_check_traits(
+    info: &MethodInfo,
+    options: &MethodOptions,
+    args: &BlockingImplArgs,
+) -> TokenStream2 {
+    let traits = send_checks(info, options, args).into_iter().map(|check| {
+        let SendCheck { name, ty, arg } = check;
+        let ty_source = type_source(&ty);
+        let message = match &arg {
+            Some(arg) => format!(
+                "argument `{arg}: {ty_source}` must be Send + 'static for strategy spawn_blocking"
+            ),
+            None => format!(
+                "return type `{ty_source}` must be Send + 'static for strategy spawn_blocking"
+            ),
+        };
+        let hint = format!(
+            "use strategy \"block_in_place\", which runs the call on the current thread, or {}",
+            owned_alternative(&ty, if arg.is_some() { "take" } else { "return" })
+        );
+        quote! {
+            #[doc(hidden)]
+            #[allow(non_camel_case_types)]
+            #[diagnostic::on_unimplemented(
+                message = #message,
+                label = "the blocking thread needs a `Send + 'static` value",
+                note = #hint,
+            )]
+            pub trait #name {
+                type Checked: ::core::marker::Send + 'static;
+                fn check(self) -> Self::Checked;
+                fn unchecked(checked: Self::Checked) -> Self;
+            }
+
+            impl<T: ::core::marker::Send + 'static> #name for T {
+                type Checked = T;
+                fn check(self) -> T {
+                    self
+                }
+                fn unchecked(checked: T) -> T {
+                    checked
+                }
+            }
+        }
+    });
+    quote! { #(#traits)* }
+}
+
+/// A suggestion for an owned, thread-safe type to use instead of `ty`.
+fn owned_alternative(ty: &Type, verb: &str) -> String {
+    let source = type_source(ty);
+    if let Type::Reference(reference) = ty {
+        let target = type_source(&reference.elem);
+        let owned = match target.as_str() {
+            "str" => "String".to_owned(),
+            "Path" => "PathBuf".to_owned(),
+            _ if target.starts_with('[') => "a `Vec`".to_owned(),
+            _ => format!("an owned `{target}`"),
+        };
+        let owned = if owned.starts_with('a') {
+            owned
+        } else {
+            format!("`{owned}`")
+        };
+        return format!("{verb} {owned} instead of `{source}`");
+    }
+    let words: Vec<_> = source
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .collect();
+    if words.contains(&"Rc") {
+        format!("{verb} an `Arc` instead of an `Rc`")
+    } else if words.contains(&"RefCell") {
+        format!("{verb} a `Mutex` or `RwLock` instead of a `RefCell`")
+    } else if words.contains(&"Cell") {
+        format!("{verb} an atomic or a `Mutex` instead of a `Cell`")
+    } else {
+        format!("{verb} an owned, thread-safe alternative to `{source}`")
+    }
+}
+
+/// `ty` as written, without the spaces `quote` puts between tokens.
+fn type_source(ty: &Type) -> String {
+    let mut source = quote!(#ty).to_string();
+    for (from, to) in [
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ::", "::"),
+        (":: ", "::"),
+        (" ,", ","),
+        ("& ", "&"),
+        ("' ", "'"),
+        ("( ", "("),
+        (" )", ")"),
+        ("[ ", "["),
+        (" ]", "]"),
+        (" ;", ";"),
+    ] {
+        source = source.replace(from, to);
+    }
+    source
+}
+
+/// Moves the checked arguments into the blocking closure of a plain `spawn_blocking` call, and
+/// its checked result out of it: the statements before the call, the call, and the
+/// adapter after `.await`.
+fn send_checked(
+    info: &MethodInfo,
+    options: &MethodOptions,
+    args: &BlockingImplArgs,
+    call: &TokenStream2,
+) -> (TokenStream2, TokenStream2, TokenStream2) {
+    let checks = send_checks(info, options, args);
+    let (send, unpack): (Vec<_>, Vec<_>) = checks
+        .iter()
+        .filter_map(|check| {
+            let SendCheck { name, ty, arg } = check;
+            let arg = arg.as_ref()?;
+            Some((
+                quote! { let #arg = <#ty as #name>::check(#arg); },
+                quote! { let #arg = <#ty as #name>::unchecked(#arg); },
+            ))
+        })
+        .unzip();
+    let returned = checks.iter().find(|check| check.arg.is_none());
+    let (call, receive) = if let Some(SendCheck { name, ty, .. }) = returned {
+        (
+            quote! {{ #(#unpack)* <#ty as #name>::check(#call) }},
+            quote! { .map(<#ty as #name>::unchecked) },
+        )
+    } else {
+        (quote! {{ #(#unpack)* #call }}, TokenStream2::new())
+    };
+    (quote! { #(#send)* }, call, receive)
+}
+
+/// The parts of a generated mock that stand in for one async method.
+struct MockedMethod {
+    field: TokenStream2,
+    init: TokenStream2,
+    methods: TokenStream2,
+    cached: bool,
+}
+
+/// The mock of one method: a `MockMethod` field answering calls with the blocking method's
+/// return value, the async method mapping it like the wrapper does, and the
+/// `expect_<method>()` and `<method>_calls()` accessors. Like the wrapper, it also has
+/// `<method>_with_priority()` for a `priority` method and `invalidate_<method>()` for a `cache`
+/// one, which the mock ignores.
+fn mock_method(
+    info: &MethodInfo,
+    options: &MethodOptions,
+    args: &BlockingImplArgs,
+) -> syn::Result<MockedMethod> {
+    let name = &info.name;
+    if options.subscribe.is_some() || info.progress.is_some() {
+        return Err(syn::Error::new_spanned(
+            name,
+            "`generate_mock` does not support `subscribe` or progress methods",
+        ));
+    }
+    let vis = &info.visibility;
+    let doc_attrs = &info.doc_attrs;
+    let (arg_names, arg_types): (Vec<_>, Vec<_>) =
+        info.args.iter().map(|(name, ty)| (name, ty)).unzip();
+    let arguments = quote! { (#(#arg_types,)*) };
+    let output = info
+        .return_type
+        .as_ref()
+        .map_or_else(|| quote! { () }, |ty| quote! { #ty });
+    let type_name = type_display_name(&args.async_type);
+    let method_name = name.to_string();
+
+    let wraps_errors = wraps_errors(options, args);
+    let call = quote! { self.#name.call((#(#arg_names,)*)).await };
+    let outcome = if wraps_errors {
+        call
+    } else {
+        quote! { #call.unwrap_or_else(|__asyncwrap_failure| ::core::panic!("{}", __asyncwrap_failure)) }
+    };
+    let (return_type, body) = map_return_type(info, wraps_errors, outcome);
+    let returns = return_type.map(|ty| quote! { -> #ty });
+
+    let with_priority = options.priority.as_ref().map(|_| {
+        let with_priority = quote::format_ident!("{}_with_priority", name);
+        let priority = priority_param(info);
+        let doc = format!("Like [`Self::{name}`]; the mock ignores the priority.");
+        quote! {
+            #[doc = #doc]
+            #[must_use = "futures do nothing unless you `.await` or poll them"]
+            #vis async fn #with_priority(
+                &self,
+                #priority: ::asyncwrap::Priority,
+                #(#arg_names: #arg_types),*
+            ) #returns {
+                let _ = #priority;
+                self.#name(#(#arg_names),*).await
+            }
+        }
+    });
+    let invalidate = options.cache.as_ref().map(|_| {
+        let invalidate = quote::format_ident!("invalidate_{}", name);
+        quote! {
+            /// Does nothing: the mock doesn't cache results.
+            #vis fn #invalidate(&self) {}
+        }
+    });
+
+    let expect = quote::format_ident!("expect_{}", name);
+    let calls = quote::format_ident!("{}_calls", name);
+    let expect_doc = format!("Adds an expectation for calls of [`Self::{name}`].");
+    let calls_doc = format!("The arguments of every call of [`Self::{name}`] so far.");
+    Ok(MockedMethod {
+        cached: options.cache.is_some(),
+        field: quote! { #name: ::asyncwrap::mock::MockMethod<#arguments, #output> },
+        init: quote! {
+            #name: ::asyncwrap::mock::MockMethod::new(
+                ::asyncwrap::CallSite::new(#type_name, #method_name),
+            )
+        },
+        methods: quote! {
+            #(#doc_attrs)*
+            #[must_use = "futures do nothing unless you `.await` or poll them"]
+            #vis async fn #name(&self, #(#arg_names: #arg_types),*) #returns {
+                #body
+            }
+
+            #with_priority
+            #invalidate
+
+            #[doc = #expect_doc]
+            #vis fn #expect(&mut self) -> &mut ::asyncwrap::mock::Expectation<#arguments, #output> {
+                self.#name.expect()
+            }
+
+            #[doc = #calls_doc]
+            #[must_use]
+            #vis fn #calls(&self) -> ::asyncwrap::mock::CallLog<'_, #arguments> {
+                self.#name.calls()
+            }
+        },
+    })
+}
+
+/// The `Mock<AsyncType>` of a wrapper with `generate_mock = true`, if the `mock` feature is
+/// enabled.
+fn generate_mock(args: &BlockingImplArgs, methods: &[MockedMethod]) -> Option<TokenStream2> {
+    if !cfg!(feature = "mock") || !args.generate_mock {
+        return None;
+    }
+    let async_type = &args.async_type;
+    let type_name = type_display_name(async_type);
+    let mock_type = Ident::new(&format!("Mock{type_name}"), async_type.span());
+    let doc = format!("A mock of [`{type_name}`] answering calls with the expectations set on it.");
+    let clear_cache = methods.iter().any(|method| method.cached).then(|| {
+        quote! {
+            /// Does nothing: the mock doesn't cache results.
+            pub fn clear_cache(&self) {}
+        }
+    });
+    let fields = methods.iter().map(|method| &method.field);
+    let inits = methods.iter().map(|method| &method.init);
+    let methods = methods.iter().map(|method| &method.methods);
+    let (circuit_field, circuit_init, circuit_methods) = if args.circuit_breaker.is_some() {
+        (
+            quote! { __asyncwrap_circuit_state: ::asyncwrap::CircuitState, },
+            quote! { __asyncwrap_circuit_state: ::asyncwrap::CircuitState::Closed, },
+            quote! {
+                /// The state set with `set_circuit_state`, `Closed` by default. The mock's
+                /// calls don't depend on it.
+                pub fn circuit_state(&self) -> ::asyncwrap::CircuitState {
+                    self.__asyncwrap_circuit_state
+                }
+
+                /// Sets the state `circuit_state` returns.
+                pub fn set_circuit_state(&mut self, state: ::asyncwrap::CircuitState) {
+                    self.__asyncwrap_circuit_state = state;
+                }
+            },
+        )
+    } else {
+        Default::default()
+    };
+
+    Some(quote! {
+        #[doc = #doc]
+        #[derive(Debug)]
+        pub struct #mock_type {
+            #(#fields,)*
+            #circuit_field
+        }
+
+        impl #mock_type {
+            /// Creates a mock without expectations, which panics on every call.
+            #[must_use]
+            pub fn new() -> Self {
+                #mock_type {
+                    #(#inits,)*
+                    #circuit_init
+                }
+            }
+
+            #(#methods)*
+            #clear_cache
+            #circuit_methods
+        }
+
+        impl ::core::default::Default for #mock_type {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+    })
+}
+
+/// Generates the circuit breaker settings and the `circuit_state` accessor of the async type.
+fn generate_circuit_breaker_items(breaker: &CircuitBreakerArgs) -> TokenStream2 {
+    let field = &breaker.field;
+    let failure_threshold = breaker.failure_threshold;
+    let open_for = duration_tokens(breaker.open_for_nanos);
+    let half_open_probes = breaker.half_open_probes;
+
+    quote! {
+        #[doc(hidden)]
+        const __ASYNCWRAP_CIRCUIT_BREAKER: ::asyncwrap::CircuitBreakerConfig =
+            ::asyncwrap::CircuitBreakerConfig::new(#failure_threshold, #open_for, #half_open_probes);
+
+        /// Returns the state of the circuit breaker guarding the wrapped methods.
+        pub fn circuit_state(&self) -> ::asyncwrap::CircuitState {
+            self.#field.state(&Self::__ASYNCWRAP_CIRCUIT_BREAKER)
+        }
+    }
+}
+
+/// Processes an impl block and generates async wrappers for marked methods.
+///
+/// # Arguments
+///
+/// The attribute takes the name of the async wrapper struct as an argument, followed by
+/// optional `key = value` settings:
+///
+/// - `strategy = "spawn_blocking" | "block_in_place"` - How the blocking call is executed
+/// - `field = "name"` - The field of the async struct holding the blocking value
+/// - `catch_unwind = bool` - With `block_in_place`, catch panics and return them as
+///   `AsyncWrapError::TaskFailed` instead of unwinding through the caller
+/// - `assert_unwind_safe = bool` - Default for the per-method `assert_unwind_safe` option
+///   (defaults to `true`)
+/// - `circuit_breaker(failure_threshold = 5, open_for = "30s", half_open_probes = 1,
+///   field = "breaker")` - Fails calls fast with `AsyncWrapError::CircuitOpen` after repeated
+///   failures. The async struct needs a `breaker: asyncwrap::CircuitBreaker` field (or the
+///   configured name), and gains a `circuit_state()` method
+/// - `observer = PATH` - A `static` implementing `asyncwrap::WrapObserver` that is notified
+///   when each blocking call starts, begins running and completes
+/// - `warn_after = "10ms"` - Report blocking calls that run longer than this, through
+///   `asyncwrap::log_slow_call` or the `fn(&asyncwrap::SlowCall)` given as `on_slow = path`.
+///   With `panic_on_slow = true`, slow calls also panic in debug builds
+/// - `state = "name"` - The `asyncwrap::WrapState` field used by `coalesce`, `cache` and
+///   `batch` methods (default `state`)
+/// - `pool = "name"` - Run blocking calls on the dedicated pool registered with
+///   `asyncwrap::PoolBuilder` under this name, instead of Tokio's blocking pool
+/// - `thread_init = path`, `thread_deinit = path` - `fn()`s run once on each thread before its
+///   first blocking call, and when that thread exits
+/// - `dispatcher = "name"` - An `asyncwrap::Dispatcher` field limiting how many blocking calls
+///   run at once; waiting calls start by their `priority`
+/// - `handle = "name"` - A `tokio::runtime::Handle` field whose runtime runs the blocking calls
+///   and timers, so the methods can be called outside of a Tokio runtime
+/// - `generate_mock = bool` - With the `mock` feature, also generate `Mock<AsyncType>`, with
+///   the same async methods answered by expectations set in tests (see `asyncwrap::mock`)
+/// - `fault_injection = bool` - With the `testing` feature, check the faults injected through
+///   `asyncwrap::testing` before each blocking call
+///
+/// # Example
+///
+/// ```ignore
+/// use asyncwrap::blocking_impl;
+/// use std::sync::Arc;
+///
+/// struct BlockingClient {
+///     // fields
+/// }
+///
+/// #[blocking_impl(AsyncClient)]
+/// impl BlockingClient {
+///     #[async_wrap]
+///     pub fn fetch(&self, id: u32) -> Result<String, Error> {
+///         // blocking implementation
+///     }
+/// }
+///
+/// // You still need to define the async struct:
+/// pub struct AsyncClient {
+///     inner: Arc<BlockingClient>,
+/// }
+///
+/// // The macro generates:
+/// // impl AsyncClient {
+/// //     pub async fn fetch(&self, id: u32) -> Result<String, AsyncWrapError<Error>> {
+/// //         let inner = Arc::clone(&self.inner);
+/// //         tokio::task::spawn_blocking(move || inner.fetch(id))
+/// //             .await
+/// //             .map_err(|e| TaskFailure::from_join_error(e, CallSite::new("AsyncClient", "fetch")))
+/// //             .map_err(AsyncWrapError::TaskFailed)?
+/// //             .map_err(AsyncWrapError::Inner)
+/// //     }
+/// // }
+/// ```
+#[proc_macro_attribute]
+pub fn blocking_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as BlockingImplArgs);
+    let mut input = parse_macro_input!(item as ItemImpl);
+
+    let async_type = &args.async_type;
+
+    let generics = &input.generics;
+    let where_clause = &input.generics.where_clause;
+    let generic_params: Vec<_> = generics.params.iter().collect();
+
+    let mut async_methods = Vec::new();
+    let mut send_checks = Vec::new();
+    let mut cache_slots = Vec::new();
+    let mut mocked_methods = Vec::new();
+    let mut errors = Vec::new();
+    if let (true, Some(params)) = (args.generate_mock, generics.lt_token) {
+        errors.push(syn::Error::new(
+            params.span,
+            "`generate_mock` does not support generic impls",
+        ));
+    }
+
+    for item in &mut input.items {
+        if let ImplItem::Fn(method) = item {
+            if has_async_wrap_attr(method) {
+                let options = validate_async_wrap_method(&method.sig)
+                    .and_then(|()| resolve_method_options(&method.attrs, &method.sig, &args));
+                match options {
+                    Err(e) => errors.push(e),
+                    Ok(options) => {
+                        if let Some(info) = extract_method_info(method) {
+                            async_methods.push(generate_async_method(&info, &options, &args));
+                            send_checks.push(generate_send_check_traits(&info, &options, &args));
+                            if options.cache.is_some() {
+                                cache_slots.push(cache_slot(&info, &args));
+                            }
+                            if args.generate_mock {
+                                match mock_method(&info, &options, &args) {
+                                    Ok(mocked) => mocked_methods.push(mocked),
+                                    Err(e) => errors.push(e),
+                                }
+                            }
+                        }
+                    }
+                }
+                remove_async_wrap_attr(method);
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        let compile_errors = errors.into_iter().map(|e| e.to_compile_error());
+        return quote! {
+            #input
+            #(#compile_errors)*
+        }
+        .into();
+    }
+
+    let impl_header = if generic_params.is_empty() {
+        quote! { impl #async_type }
+    } else {
+        quote! { impl<#(#generic_params),*> #async_type #where_clause }
+    };
+    let shared_field = (!async_methods.is_empty())
+        .then(|| generate_shared_field(&args, &input.self_ty, &input.generics))
+        .flatten();
+
+    if let Some(breaker) = &args.circuit_breaker {
+        async_methods.push(generate_circuit_breaker_items(breaker));
+    }
+    async_methods.extend(generate_clear_cache(&cache_slots));
+
+    let mock = generate_mock(&args, &mocked_methods);
+    let output = quote! {
+        #input
+        const _: () = {
+            #(#send_checks)*
+            #shared_field
+            #impl_header {
+                #(#async_methods)*
+            }
+        };
+        #mock
+    };
+
+    output.into()
+}
+
+/// Marks an async method for blocking wrapper generation.
+///
+/// This attribute should be placed on `async fn` methods taking `&self` within an
+/// `#[async_impl]` block. The method will have a blocking version generated in the
+/// corresponding blocking wrapper struct.
+#[proc_macro_attribute]
+pub fn blocking_wrap(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let Ok(method) = syn::parse::<ImplItemFn>(item.clone()) else {
+        return item;
+    };
+
+    if let Err(e) = validate_blocking_wrap_method(&method) {
+        return e.to_compile_error().into();
+    }
+
+    item
+}
+
+struct AsyncImplArgs {
+    blocking_type: Type,
+    field: Ident,
+    runtime: Ident,
+}
+
+impl Parse for AsyncImplArgs {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let blocking_type: Type = input.parse()?;
+
+        let mut field = Ident::new("inner", proc_macro2::Span::call_site());
+        let mut runtime = Ident::new("runtime", proc_macro2::Span::call_site());
+
+        while input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+            let ident: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let value: syn::LitStr = input.parse()?;
+
+            if ident == "field" {
+                field = Ident::new(&value.value(), value.span());
+            } else if ident == "runtime" {
+                runtime = Ident::new(&value.value(), value.span());
+            } else {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "expected `field` or `runtime`",
+                ));
+            }
+        }
+
+        Ok(AsyncImplArgs {
+            blocking_type,
+            field,
+            runtime,
+        })
+    }
+}
+
+fn has_blocking_wrap_attr(method: &ImplItemFn) -> bool {
+    method
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("blocking_wrap"))
+}
+
+fn validate_blocking_wrap_method(method: &ImplItemFn) -> syn::Result<()> {
+    if method.sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            method.sig.fn_token,
+            "#[blocking_wrap] requires an `async fn`",
+        ));
+    }
+
+    match method.sig.inputs.first() {
+        Some(arg) if is_self_by_ref(arg) => Ok(()),
+        Some(arg) => Err(syn::Error::new_spanned(
+            arg,
+            "#[blocking_wrap] requires methods taking `&self`",
+        )),
+        None => Err(syn::Error::new_spanned(
+            &method.sig,
+            "#[blocking_wrap] requires methods taking `&self`",
+        )),
+    }
+}
+
+fn generate_blocking_method(info: &MethodInfo, args: &AsyncImplArgs) -> TokenStream2 {
+    let name = &info.name;
+    let vis = &info.visibility;
+    let doc_attrs = &info.doc_attrs;
+    let field = &args.field;
+    let runtime = &args.runtime;
+    let arg_names: Vec<_> = info.args.iter().map(|(name, _)| name).collect();
+    let arg_types: Vec<_> = info.args.iter().map(|(_, ty)| ty).collect();
+    let type_name = type_display_name(&args.blocking_type);
+    let method_name = name.to_string();
+
+    let block_on = quote! {
+        ::asyncwrap::BlockOn::block_on(&self.#runtime, self.#field.#name(#(#arg_names),*))
+    };
+
+    let (return_type, body) = if info.is_result {
+        let inner_return = info.return_type.as_ref().unwrap();
+        (
+            quote! { ::asyncwrap::AsyncWrapResult<#inner_return> },
+            quote! { #block_on.map_err(::asyncwrap::AsyncWrapError::Inner) },
+        )
+    } else {
+        let ret_ty = info
+            .return_type
+            .as_ref()
+            .map_or_else(|| quote! { () }, |ty| quote! { #ty });
+        (
+            quote! {
+                ::core::result::Result<
+                    #ret_ty,
+                    ::asyncwrap::AsyncWrapError<::core::convert::Infallible>,
+                >
+            },
+            quote! { ::core::result::Result::Ok(#block_on) },
+        )
+    };
+
+    quote! {
+        #(#doc_attrs)*
+        #vis fn #name(&self, #(#arg_names: #arg_types),*) -> #return_type {
+            const __ASYNCWRAP_CALL_SITE: ::asyncwrap::CallSite =
+                ::asyncwrap::CallSite::new(#type_name, #method_name);
+            ::asyncwrap::__private::ensure_outside_runtime(__ASYNCWRAP_CALL_SITE)?;
+            #body
+        }
+    }
+}
+
+/// Processes an impl block of async methods and generates blocking wrappers for marked methods.
+///
+/// This is the mirror of [`macro@blocking_impl`], for calling a native async client from
+/// synchronous code such as CLI tools or FFI callbacks.
+///
+/// # Arguments
+///
+/// The attribute takes the name of the blocking wrapper struct as an argument, followed by
+/// optional `key = value` settings:
+///
+/// - `field = "name"` - The field of the blocking struct holding the async value
+/// - `runtime = "name"` - The field of the blocking struct implementing `asyncwrap::BlockOn`,
+///   such as a `tokio::runtime::Runtime` or `Handle`
+///
+/// The generated methods return `AsyncWrapError::InsideRuntime` instead of blocking when they
+/// are called from a thread inside a Tokio runtime, where `block_on` would panic or deadlock.
+/// This includes `spawn_blocking` threads, which Tokio doesn't distinguish from its workers.
+///
+/// # Example
+///
+/// ```ignore
+/// use asyncwrap::async_impl;
+///
+/// #[async_impl(BlockingClient)]
+/// impl AsyncClient {
+///     #[blocking_wrap]
+///     pub async fn fetch(&self, id: u32) -> Result<String, Error> {
+///         // async implementation
+///     }
+/// }
+///
+/// pub struct BlockingClient {
+///     inner: AsyncClient,
+///     runtime: tokio::runtime::Runtime,
+/// }
+///
+/// // The macro generates:
+/// // impl BlockingClient {
+/// //     pub fn fetch(&self, id: u32) -> Result<String, AsyncWrapError<Error>> {
+/// //         ensure_outside_runtime(CallSite::new("BlockingClient", "fetch"))?;
+/// //         self.runtime.block_on(self.inner.fetch(id)).map_err(AsyncWrapError::Inner)
+/// //     }
+/// // }
+/// ```
+#[proc_macro_attribute]
+pub fn async_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as AsyncImplArgs);
+    let mut input = parse_macro_input!(item as ItemImpl);
+
+    let blocking_type = &args.blocking_type;
+    let generic_params: Vec<_> = input.generics.params.iter().cloned().collect();
+    let where_clause = input.generics.where_clause.clone();
+
+    let mut blocking_methods = Vec::new();
+    let mut errors = Vec::new();
+
+    for item in &mut input.items {
+        if let ImplItem::Fn(method) = item {
+            if has_blocking_wrap_attr(method) {
+                match validate_blocking_wrap_method(method) {
+                    Err(e) => errors.push(e),
+                    Ok(()) => {
+                        if let Some(info) = extract_method_info(method) {
+                            blocking_methods.push(generate_blocking_method(&info, &args));
+                        }
+                    }
+                }
+                method
+                    .attrs
+                    .retain(|attr| !attr.path().is_ident("blocking_wrap"));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        let compile_errors = errors.into_iter().map(|e| e.to_compile_error());
+        return quote! {
+            #input
             #(#compile_errors)*
         }
         .into();
     }
 
-    let async_impl = if generic_params.is_empty() {
+    let blocking_impl = if generic_params.is_empty() {
         quote! {
-            impl #async_type {
-                #(#async_methods)*
+            impl #blocking_type {
+                #(#blocking_methods)*
             }
         }
     } else {
         quote! {
-            impl<#(#generic_params),*> #async_type #where_clause {
-                #(#async_methods)*
+            impl<#(#generic_params),*> #blocking_type #where_clause {
+                #(#blocking_methods)*
             }
         }
     };
 
     let output = quote! {
         #input
-        #async_impl
+        #blocking_impl
     };
 
     output.into()
 }
+
+/// The visibility of an async function in the `r#async` submodule, so that it is visible
+/// wherever the blocking function is.
+fn submodule_visibility(vis: &Visibility) -> Visibility {
+    match vis {
+        Visibility::Public(_) => vis.clone(),
+        Visibility::Restricted(restricted) if restricted.path.is_ident("crate") => vis.clone(),
+        _ => syn::parse_quote!(pub(super)),
+    }
+}
+
+/// Processes a module and generates an `r#async` submodule with async wrappers for its
+/// marked functions.
+///
+/// # Arguments
+///
+/// The attribute takes the same optional `key = value` settings as [`macro@blocking_impl`],
+/// except `field` and `circuit_breaker`, which only apply to methods.
+///
+/// # Example
+///
+/// ```ignore
+/// use asyncwrap::blocking_mod;
+///
+/// #[blocking_mod]
+/// pub mod codec {
+///     #[async_wrap]
+///     pub fn decode_image(bytes: Vec<u8>) -> Result<Image, Error> {
+///         // blocking implementation
+///     }
+/// }
+///
+/// // The macro generates, inside `codec`:
+/// // pub mod r#async {
+/// //     pub async fn decode_image(bytes: Vec<u8>) -> Result<Image, AsyncWrapError<Error>> {
+/// //         tokio::task::spawn_blocking(move || super::decode_image(bytes)) /* ... */
+/// //     }
+/// // }
+/// ```
+#[proc_macro_attribute]
+pub fn blocking_mod(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let parser = |input: ParseStream<'_>| {
+        BlockingImplArgs::parse_settings(syn::parse_quote!(r#async), input)
+    };
+    let args = parse_macro_input!(attr with parser);
+    let mut input = parse_macro_input!(item as syn::ItemMod);
+
+    if args.circuit_breaker.is_some() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`circuit_breaker` is not supported by `#[blocking_mod]`",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let Some((_, items)) = &mut input.content else {
+        return syn::Error::new_spanned(&input, "#[blocking_mod] requires an inline module")
+            .to_compile_error()
+            .into();
+    };
+
+    let mut async_functions = Vec::new();
+    let mut errors = Vec::new();
+
+    for item in items.iter_mut() {
+        if let syn::Item::Fn(function) = item {
+            if function
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("async_wrap"))
+            {
+                let name = &function.sig.ident;
+                let kind = CallKind::Function {
+                    path: quote! { super::#name },
+                    async_name: name.clone(),
+                };
+                let wrapper = parse_method_args(&function.attrs).and_then(|method_args| {
+                    generate_async_function(
+                        kind,
+                        &function.sig,
+                        submodule_visibility(&function.vis),
+                        &function.attrs,
+                        method_args,
+                        &args,
+                    )
+                });
+                match wrapper {
+                    Ok(wrapper) => async_functions.push(wrapper),
+                    Err(e) => errors.push(e),
+                }
+                function
+                    .attrs
+                    .retain(|attr| !attr.path().is_ident("async_wrap"));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        let compile_errors = errors.into_iter().map(|e| e.to_compile_error());
+        return quote! {
+            #input
+            #(#compile_errors)*
+        }
+        .into();
+    }
+
+    items.push(syn::parse_quote! {
+        /// Async versions of the blocking functions in this module.
+        pub mod r#async {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #(#async_functions)*
+        }
+    });
+
+    quote! { #input }.into()
+}
+
+/// The input of `wrap_foreign!`.
+struct ForeignWrap {
+    args: BlockingImplArgs,
+    foreign_type: Type,
+    methods: Vec<ForeignMethod>,
+}
+
+/// A method signature declared inside `wrap_foreign!`.
+struct ForeignMethod {
+    attrs: Vec<syn::Attribute>,
+    vis: Visibility,
+    sig: syn::Signature,
+    by_ref: Vec<Ident>,
+}
+
+/// Removes `#[by_ref]` from the arguments of `sig`, returning the names of the marked arguments.
+fn take_by_ref_args(sig: &mut syn::Signature) -> Vec<Ident> {
+    let mut by_ref = Vec::new();
+    for arg in &mut sig.inputs {
+        if let FnArg::Typed(pat_type) = arg {
+            let before = pat_type.attrs.len();
+            pat_type
+                .attrs
+                .retain(|attr| !attr.path().is_ident("by_ref"));
+            if pat_type.attrs.len() != before {
+                if let Pat::Ident(pat_ident) = &*pat_type.pat {
+                    by_ref.push(pat_ident.ident.clone());
+                }
+            }
+        }
+    }
+    by_ref
+}
+
+impl Parse for ForeignWrap {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let async_type: Type = input.parse()?;
+        input.parse::<Token![for]>()?;
+        let foreign_type: Type = input.parse()?;
+        if input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+        }
+        let args = BlockingImplArgs::parse_settings(async_type, input)?;
+
+        let content;
+        syn::braced!(content in input);
+        let mut methods = Vec::new();
+        while !content.is_empty() {
+            let attrs = content.call(syn::Attribute::parse_outer)?;
+            let vis: Visibility = content.parse()?;
+            let mut sig: syn::Signature = content.parse()?;
+            if content.peek(syn::token::Brace) {
+                return Err(content.error(
+                    "wrap_foreign! takes method signatures only; end the signature with `;`",
+                ));
+            }
+            content.parse::<Token![;]>()?;
+            let by_ref = take_by_ref_args(&mut sig);
+            methods.push(ForeignMethod {
+                attrs,
+                vis,
+                sig,
+                by_ref,
+            });
+        }
+
+        Ok(ForeignWrap {
+            args,
+            foreign_type,
+            methods,
+        })
+    }
+}
+
+/// Generates async wrappers for methods of a type from another crate.
+///
+/// `#[blocking_impl]` needs to annotate the impl block, which isn't possible for types such as
+/// `rusqlite::Connection`. `wrap_foreign!` takes the signatures of the methods to wrap instead,
+/// and generates the same async methods `#[blocking_impl]` would.
+///
+/// # Arguments
+///
+/// The async wrapper struct, `for`, and the foreign type, optionally followed by the same
+/// `key = value` settings as [`macro@blocking_impl`]. Each signature may carry
+/// `#[async_wrap(...)]` options and doc comments.
+///
+/// The generated methods call the foreign method with the declared argument types. Mark an
+/// argument with `#[by_ref]` to take it by value in the async method and pass a reference to
+/// the foreign method, e.g. `#[by_ref] sql: String` for a method taking `&str`.
+///
+/// # Example
+///
+/// ```ignore
+/// asyncwrap::wrap_foreign! {
+///     AsyncConn for rusqlite::Connection, strategy = "block_in_place", field = "conn" {
+///         /// Executes a statement.
+///         pub fn execute(&self, #[by_ref] sql: String, params: ()) -> rusqlite::Result<usize>;
+///
+///         #[async_wrap(catch_unwind = true)]
+///         pub fn is_autocommit(&self) -> bool;
+///     }
+/// }
+///
+/// pub struct AsyncConn {
+///     conn: rusqlite::Connection,
+/// }
+/// ```
+#[proc_macro]
+pub fn wrap_foreign(input: TokenStream) -> TokenStream {
+    let ForeignWrap {
+        args,
+        foreign_type,
+        methods,
+    } = parse_macro_input!(input as ForeignWrap);
+
+    let mut async_methods = Vec::new();
+    let mut send_checks = Vec::new();
+    let mut cache_slots = Vec::new();
+    let mut mocked_methods = Vec::new();
+    let mut errors = Vec::new();
+
+    for method in &methods {
+        let options = validate_async_wrap_method(&method.sig)
+            .and_then(|()| resolve_method_options(&method.attrs, &method.sig, &args));
+        match options {
+            Err(e) => errors.push(e),
+            Ok(options) => {
+                let kind = CallKind::Method {
+                    self_ty: Some(foreign_type.clone()),
+                    by_ref: method.by_ref.clone(),
+                };
+                let info = signature_info(kind, &method.sig, method.vis.clone(), &method.attrs);
+                async_methods.push(generate_async_method(&info, &options, &args));
+                send_checks.push(generate_send_check_traits(&info, &options, &args));
+                if options.cache.is_some() {
+                    cache_slots.push(cache_slot(&info, &args));
+                }
+                if args.generate_mock {
+                    match mock_method(&info, &options, &args) {
+                        Ok(mocked) => mocked_methods.push(mocked),
+                        Err(e) => errors.push(e),
+                    }
+                }
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        let compile_errors = errors.into_iter().map(|e| e.to_compile_error());
+        return quote! { #(#compile_errors)* }.into();
+    }
+
+    let async_type = &args.async_type;
+    let shared_field = (!async_methods.is_empty())
+        .then(|| generate_shared_field(&args, &foreign_type, &syn::Generics::default()))
+        .flatten();
+
+    if let Some(breaker) = &args.circuit_breaker {
+        async_methods.push(generate_circuit_breaker_items(breaker));
+    }
+    async_methods.extend(generate_clear_cache(&cache_slots));
+
+    let mock = generate_mock(&args, &mocked_methods);
+    quote! {
+        const _: () = {
+            #(#send_checks)*
+            #shared_field
+            impl #async_type {
+                #(#async_methods)*
+            }
+        };
+        #mock
+    }
+    .into()
+}
+
+/// The input of `wrap_extern!`.
+struct ExternWrap {
+    blocking_type: Ident,
+    async_type: Ident,
+    args: BlockingImplArgs,
+    handle: Type,
+    code: Type,
+    check: syn::Path,
+    error: Type,
+    prefix: Option<String>,
+    sys: Option<syn::Path>,
+    block: syn::ItemForeignMod,
+}
+
+impl Parse for ExternWrap {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let blocking_type: Ident = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let async_type: Ident = input.parse()?;
+
+        let mut handle = None;
+        let mut code = None;
+        let mut check = None;
+        let mut error = None;
+        let mut prefix = None;
+        let mut sys = None;
+
+        // The `wrap_extern!` settings can be mixed with the `#[blocking_impl]` ones, which are
+        // collected and parsed together once the extern ones are known.
+        let mut settings = TokenStream2::new();
+        while input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+            if at_settings_end(input) {
+                break;
+            }
+            let ident: Ident = input.fork().parse()?;
+            let key = ident.to_string();
+            if ["handle", "code", "check", "error", "prefix", "sys"].contains(&key.as_str()) {
+                input.parse::<Ident>()?;
+                input.parse::<Token![=]>()?;
+                if ident == "handle" {
+                    if input.peek(syn::LitStr) {
+                        return Err(syn::Error::new(
+                            input.span(),
+                            "`wrap_extern!` does not support the runtime `handle` option; \
+                             `handle = Type` is the C handle type",
+                        ));
+                    }
+                    handle = Some(input.parse()?);
+                } else if ident == "code" {
+                    code = Some(input.parse()?);
+                } else if ident == "check" {
+                    check = Some(input.parse()?);
+                } else if ident == "error" {
+                    error = Some(input.parse()?);
+                } else if ident == "prefix" {
+                    prefix = Some(input.parse::<syn::LitStr>()?.value());
+                } else {
+                    sys = Some(input.parse()?);
+                }
+            } else if ["state", "dispatcher"].contains(&key.as_str()) {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    format!(
+                        "`{key}` is not supported by `wrap_extern!`, whose wrappers have no \
+                         field for it"
+                    ),
+                ));
+            } else if ident == "generate_mock" {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "`generate_mock` is not supported by `wrap_extern!`",
+                ));
+            } else {
+                while !input.peek(Token![,]) && !at_settings_end(input) {
+                    settings.extend([input.parse::<proc_macro2::TokenTree>()?]);
+                }
+                settings.extend(quote! { , });
+            }
+        }
+
+        let args = syn::parse::Parser::parse2(
+            |settings: ParseStream<'_>| {
+                BlockingImplArgs::parse_settings(syn::parse_quote!(#async_type), settings)
+            },
+            settings,
+        )?;
+        let block: syn::ItemForeignMod = input.parse()?;
+
+        let (Some(handle), Some(code), Some(check), Some(error)) = (handle, code, check, error)
+        else {
+            return Err(syn::Error::new_spanned(
+                &blocking_type,
+                "wrap_extern! requires `handle = Type`, `code = Type`, `check = path` and \
+                 `error = Type`",
+            ));
+        };
+
+        Ok(ExternWrap {
+            blocking_type,
+            async_type,
+            args,
+            handle,
+            code,
+            check,
+            error,
+            prefix,
+            sys,
+            block,
+        })
+    }
+}
+
+/// A function of the `extern` block that takes the handle as its first argument.
+struct ExternFunction<'a> {
+    function: &'a syn::ForeignItemFn,
+    method: Ident,
+    args: Vec<(Ident, Type)>,
+    output: ExternOutput<'a>,
+    takes_pointers: bool,
+}
+
+/// What a function of the `extern` block returns.
+enum ExternOutput<'a> {
+    Unit,
+    /// A status code, mapped to a `Result` by `check`.
+    Code,
+    /// Any other value, returned as is.
+    Value(&'a Type),
+}
+
+impl ExternFunction<'_> {
+    /// Whether the function gets an async method: raw pointers can't be sent to the blocking
+    /// thread, and their safety is up to the caller.
+    fn is_async(&self) -> bool {
+        !self.takes_pointers && !matches!(self.output, ExternOutput::Value(Type::Ptr(_)))
+    }
+}
+
+impl ExternWrap {
+    fn functions(&self) -> Vec<ExternFunction<'_>> {
+        let handle_name = type_display_name(&self.handle);
+        let code_name = type_display_name(&self.code);
+        let mut functions = Vec::new();
+
+        for item in &self.block.items {
+            let syn::ForeignItem::Fn(function) = item else {
+                continue;
+            };
+            let sig = &function.sig;
+            let Some(FnArg::Typed(first)) = sig.inputs.first() else {
+                continue;
+            };
+            let takes_handle = matches!(
+                &*first.ty,
+                Type::Ptr(ptr) if type_display_name(&ptr.elem) == handle_name
+            );
+            if !takes_handle || sig.variadic.is_some() {
+                continue;
+            }
+
+            let name = sig.ident.to_string();
+            let method = self
+                .prefix
+                .as_deref()
+                .and_then(|prefix| name.strip_prefix(prefix))
+                .unwrap_or(&name);
+            let args: Vec<(Ident, Type)> = sig
+                .inputs
+                .iter()
+                .skip(1)
+                .enumerate()
+                .filter_map(|(index, arg)| match arg {
+                    FnArg::Typed(pat_type) => {
+                        let name = match &*pat_type.pat {
+                            Pat::Ident(pat_ident) => pat_ident.ident.clone(),
+                            _ => quote::format_ident!("arg{}", index + 1),
+                        };
+                        Some((name, (*pat_type.ty).clone()))
+                    }
+                    FnArg::Receiver(_) => None,
+                })
+                .collect();
+            let output = match &sig.output {
+                ReturnType::Default => ExternOutput::Unit,
+                ReturnType::Type(_, ty) => match &**ty {
+                    Type::Tuple(tuple) if tuple.elems.is_empty() => ExternOutput::Unit,
+                    ty if type_display_name(ty) == code_name => ExternOutput::Code,
+                    ty => ExternOutput::Value(ty),
+                },
+            };
+
+            functions.push(ExternFunction {
+                function,
+                method: Ident::new(method, sig.ident.span()),
+                takes_pointers: args.iter().any(|(_, ty)| matches!(ty, Type::Ptr(_))),
+                args,
+                output,
+            });
+        }
+
+        functions
+    }
+
+    /// The blocking method calling `function`, and its signature as a `&self` method.
+    fn blocking_method(&self, function: &ExternFunction<'_>) -> (TokenStream2, syn::Signature) {
+        let ExternFunction {
+            function: item,
+            method,
+            args,
+            output,
+            takes_pointers,
+        } = function;
+        let name = &item.sig.ident;
+        let docs = item.attrs.iter().filter(|attr| attr.path().is_ident("doc"));
+        let arg_names: Vec<_> = args.iter().map(|(name, _)| name).collect();
+        let arg_types: Vec<_> = args.iter().map(|(_, ty)| ty).collect();
+        let function_path = self
+            .sys
+            .as_ref()
+            .map_or_else(|| quote! { #name }, |sys| quote! { #sys::#name });
+        let call = quote! {
+            unsafe { #function_path(self.handle.as_ptr(), #(#arg_names),*) }
+        };
+
+        let check = &self.check;
+        let error = &self.error;
+        let (output, body) = match output {
+            ExternOutput::Unit => (TokenStream2::new(), call),
+            ExternOutput::Code => (
+                quote! { -> ::core::result::Result<(), #error> },
+                quote! { #check(#call) },
+            ),
+            ExternOutput::Value(ty) => (quote! { -> #ty }, call),
+        };
+
+        let sig: syn::Signature =
+            syn::parse_quote! { fn #method(&self, #(#arg_names: #arg_types),*) #output };
+        let function_doc = format!("Calls `{name}` with the wrapped handle.");
+        let method = if *takes_pointers {
+            quote! {
+                #(#docs)*
+                #[doc = #function_doc]
+                ///
+                /// # Safety
+                ///
+                /// The pointer arguments must be valid as required by the C library.
+                pub unsafe #sig {
+                    #body
+                }
+            }
+        } else {
+            quote! {
+                #(#docs)*
+                #[doc = #function_doc]
+                pub #sig {
+                    #body
+                }
+            }
+        };
+        (method, sig)
+    }
+}
+
+/// Generates a safe blocking wrapper type and an async wrapper from C function declarations.
+///
+/// This consumes an `extern "C"` block, such as bindgen output, and wraps every function whose
+/// first argument is a pointer to the handle type. Use `asyncwrap::build::ExternWrapper` (with
+/// the `build` feature) to generate the invocation from bindgen output in a build script.
+///
+/// # Arguments
+///
+/// The blocking and async wrapper type names, followed by:
+///
+/// - `handle = Type` - The opaque handle type the functions take a `*mut Type` to
+/// - `code = Type` - The status code type the functions return, e.g. `c_int`
+/// - `check = path` - A `fn(Code) -> Result<(), Error>` mapping return codes to a `Result`
+/// - `error = Type` - The error type returned by `check`
+/// - `prefix = "sdk_"` - Stripped from the function names to name the methods
+/// - `sys = path` - The module declaring the functions; when set, the `extern` block is only
+///   read and not emitted again
+///
+/// and any of the [`macro@blocking_impl`] settings except `state`, `dispatcher`, `handle` and
+/// `generate_mock`, which refer to fields the generated wrapper doesn't have. The settings can
+/// be given in any order.
+///
+/// Functions returning a `code` get methods returning `Result<(), Error>` through `check`; other
+/// return values are passed through. Functions taking raw pointers besides the handle get
+/// `unsafe` blocking methods and no async method, and functions returning raw pointers get no
+/// async method either.
+///
+/// # Example
+///
+/// ```ignore
+/// asyncwrap::wrap_extern! {
+///     Camera, AsyncCamera, handle = CrHandle, code = c_int, check = check_status,
+///     error = SdkError, prefix = "sdk_"
+///     extern "C" {
+///         pub fn sdk_connect(handle: *mut CrHandle, timeout_ms: c_int) -> c_int;
+///     }
+/// }
+///
+/// let camera = unsafe { Camera::from_raw(raw_handle) };
+/// let camera = AsyncCamera::new(camera);
+/// camera.connect(1000).await?;
+/// ```
+#[proc_macro]
+pub fn wrap_extern(input: TokenStream) -> TokenStream {
+    let wrap = parse_macro_input!(input as ExternWrap);
+    let ExternWrap {
+        blocking_type,
+        async_type,
+        args,
+        handle,
+        sys,
+        block,
+        ..
+    } = &wrap;
+
+    let mut blocking_methods = Vec::new();
+    let mut async_methods = Vec::new();
+    let mut send_checks = Vec::new();
+    for function in wrap.functions() {
+        let (blocking_method, sig) = wrap.blocking_method(&function);
+        blocking_methods.push(blocking_method);
+        if !function.is_async() {
+            continue;
+        }
+
+        let kind = CallKind::Method {
+            self_ty: None,
+            by_ref: Vec::new(),
+        };
+        let attrs = &function.function.attrs;
+        let info = signature_info(kind, &sig, syn::parse_quote!(pub), attrs);
+        match resolve_options(MethodArgs::default(), &sig, args) {
+            Ok(options) => {
+                async_methods.push(generate_async_method(&info, &options, args));
+                send_checks.push(generate_send_check_traits(&info, &options, args));
+            }
+            Err(e) => return e.to_compile_error().into(),
+        }
+    }
+
+    let blocking_ty: Type = syn::parse_quote!(#blocking_type);
+    let shared_field = (!async_methods.is_empty())
+        .then(|| generate_shared_field(args, &blocking_ty, &syn::Generics::default()))
+        .flatten();
+
+    let field = &args.field;
+    let (inner_type, inner_value) = match args.strategy {
+        Strategy::SpawnBlocking => (
+            quote! { ::std::sync::Arc<#blocking_type> },
+            quote! { ::std::sync::Arc::new(blocking) },
+        ),
+        Strategy::BlockInPlace => (quote! { #blocking_type }, quote! { blocking }),
+    };
+    let (breaker_field, breaker_value) = match &args.circuit_breaker {
+        Some(breaker) => {
+            async_methods.push(generate_circuit_breaker_items(breaker));
+            let breaker_field = &breaker.field;
+            (
+                quote! { #breaker_field: ::asyncwrap::CircuitBreaker, },
+                quote! { #breaker_field: ::asyncwrap::CircuitBreaker::new(), },
+            )
+        }
+        None => (TokenStream2::new(), TokenStream2::new()),
+    };
+    let extern_block = sys.is_none().then_some(block);
+    let blocking_doc = format!("Blocking wrapper around a `{}` handle.", quote!(#handle));
+    let async_doc = format!("Async wrapper around [`{blocking_type}`].");
+
+    quote! {
+        #extern_block
+
+        #[doc = #blocking_doc]
+        pub struct #blocking_type {
+            handle: ::asyncwrap::ffi::SendPtr<#handle>,
+        }
+
+        #[allow(unsafe_code)]
+        impl #blocking_type {
+            /// Wraps a handle created by the C library.
+            ///
+            /// # Safety
+            ///
+            /// `handle` must stay valid for the lifetime of the returned value, and the C
+            /// library must allow it to be used from any thread, including concurrently.
+            pub unsafe fn from_raw(handle: *mut #handle) -> Self {
+                #blocking_type {
+                    handle: unsafe { ::asyncwrap::ffi::SendPtr::new(handle) },
+                }
+            }
+
+            /// Returns the raw handle.
+            pub fn as_ptr(&self) -> *mut #handle {
+                self.handle.as_ptr()
+            }
+
+            #(#blocking_methods)*
+        }
+
+        #[doc = #async_doc]
+        pub struct #async_type {
+            #field: #inner_type,
+            #breaker_field
+        }
+
+        const _: () = {
+            #(#send_checks)*
+            #shared_field
+            impl #async_type {
+                /// Wraps a blocking handle wrapper for use from async code.
+                pub fn new(blocking: #blocking_type) -> Self {
+                    #async_type {
+                        #field: #inner_value,
+                        #breaker_value
+                    }
+                }
+
+                #(#async_methods)*
+            }
+        };
+    }
+    .into()
+}
@@ -0,0 +1,152 @@
+//! Request batching for `#[async_wrap(batch(...))]`.
+
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+use tokio::sync::oneshot;
+
+use crate::{CallSite, TaskFailure};
+
+type BatchCall<A, R> = Box<dyn FnOnce(Vec<A>) -> Vec<R> + Send>;
+
+/// The calls of one batched method that are waiting to be sent to its batch method.
+pub struct Batcher<A, R> {
+    pending: Mutex<Pending<A, R>>,
+}
+
+struct Pending<A, R> {
+    batch: Option<Batch<A, R>>,
+    next_id: u64,
+}
+
+struct Batch<A, R> {
+    id: u64,
+    args: Vec<A>,
+    waiters: Vec<oneshot::Sender<Result<R, TaskFailure>>>,
+    call: BatchCall<A, R>,
+    call_site: CallSite,
+    runtime: Handle,
+}
+
+impl<A, R> Default for Batcher<A, R> {
+    fn default() -> Self {
+        Batcher {
+            pending: Mutex::new(Pending {
+                batch: None,
+                next_id: 0,
+            }),
+        }
+    }
+}
+
+impl<A: Send + 'static, R: Send + 'static> Batcher<A, R> {
+    fn lock(&self) -> MutexGuard<'_, Pending<A, R>> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Adds `arg` to the pending batch and resolves to its result.
+    ///
+    /// The batch is passed to `call` on the blocking pool of `runtime` once it holds `max_size` arguments,
+    /// or `max_delay` after its first argument arrived. `call` must return one result per
+    /// argument, in order. Only the `call` of a batch's first argument is used.
+    ///
+    /// # Errors
+    ///
+    /// Resolves to a [`TaskFailure`] shared by the whole batch if `call` panics or returns the
+    /// wrong number of results, or if the batch is dropped before it runs.
+    pub fn submit(
+        self: &Arc<Self>,
+        runtime: &Handle,
+        arg: A,
+        max_size: usize,
+        max_delay: Duration,
+        call_site: CallSite,
+        call: impl FnOnce(Vec<A>) -> Vec<R> + Send + 'static,
+    ) -> impl Future<Output = Result<R, TaskFailure>> {
+        let (sender, receiver) = oneshot::channel();
+        let mut pending = self.lock();
+        let batch = if let Some(batch) = &mut pending.batch {
+            batch
+        } else {
+            let id = pending.next_id;
+            pending.next_id += 1;
+            let batcher = Arc::clone(self);
+            tokio::spawn(async move {
+                tokio::time::sleep(max_delay).await;
+                batcher.flush(id);
+            });
+            pending.batch.insert(Batch {
+                id,
+                args: Vec::with_capacity(max_size),
+                waiters: Vec::with_capacity(max_size),
+                call: Box::new(call),
+                call_site,
+                runtime: runtime.clone(),
+            })
+        };
+        batch.args.push(arg);
+        batch.waiters.push(sender);
+        if batch.args.len() >= max_size {
+            if let Some(batch) = pending.batch.take() {
+                batch.run();
+            }
+        }
+        drop(pending);
+
+        async move {
+            receiver
+                .await
+                .unwrap_or_else(|_| Err(TaskFailure::cancelled(call_site)))
+        }
+    }
+
+    /// Runs the pending batch if it is still batch `id`.
+    fn flush(&self, id: u64) {
+        let mut pending = self.lock();
+        if pending.batch.as_ref().is_some_and(|batch| batch.id == id) {
+            if let Some(batch) = pending.batch.take() {
+                drop(pending);
+                batch.run();
+            }
+        }
+    }
+}
+
+impl<A: Send + 'static, R: Send + 'static> Batch<A, R> {
+    fn run(self) {
+        let Batch {
+            args,
+            waiters,
+            call,
+            call_site,
+            runtime,
+            ..
+        } = self;
+        // Detached: callers that stopped waiting don't cancel the others' results.
+        drop(runtime.spawn_blocking(move || {
+            let expected = args.len();
+            let failure = match panic::catch_unwind(AssertUnwindSafe(|| call(args))) {
+                Ok(results) if results.len() == expected => {
+                    for (waiter, result) in waiters.into_iter().zip(results) {
+                        let _ = waiter.send(Ok(result));
+                    }
+                    return;
+                }
+                Ok(results) => TaskFailure::from_panic(
+                    Box::new(format!(
+                        "batch method returned {} results for {expected} arguments",
+                        results.len()
+                    )),
+                    call_site,
+                ),
+                Err(payload) => TaskFailure::from_panic(payload, call_site),
+            };
+            for waiter in waiters {
+                let _ = waiter.send(Err(failure.clone()));
+            }
+        }));
+    }
+}
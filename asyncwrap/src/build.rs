@@ -0,0 +1,228 @@
+//! Build-script support for generating [`wrap_extern!`](crate::wrap_extern) invocations from
+//! bindgen output.
+//!
+//! ```ignore
+//! // build.rs
+//! let out = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+//! bindgen::builder()
+//!     .header("sdk.h")
+//!     .generate()
+//!     .unwrap()
+//!     .write_to_file(out.join("bindings.rs"))
+//!     .unwrap();
+//!
+//! asyncwrap::build::ExternWrapper::new("Camera", "AsyncCamera")
+//!     .handle("CrHandle")
+//!     .code("std::os::raw::c_int")
+//!     .check("crate::check_status")
+//!     .error("crate::SdkError")
+//!     .prefix("sdk_")
+//!     .sys("crate::sys")
+//!     .write(out.join("bindings.rs"), out.join("wrappers.rs"))
+//!     .unwrap();
+//!
+//! // lib.rs
+//! mod sys {
+//!     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+//! }
+//! include!(concat!(env!("OUT_DIR"), "/wrappers.rs"));
+//! ```
+
+use std::io;
+use std::path::Path;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Generates a `wrap_extern!` invocation for the functions declared in bindgen output.
+#[derive(Debug, Clone)]
+pub struct ExternWrapper {
+    blocking_type: String,
+    async_type: String,
+    handle: Option<String>,
+    code: Option<String>,
+    check: Option<String>,
+    error: Option<String>,
+    prefix: Option<String>,
+    sys: Option<String>,
+    settings: Vec<String>,
+}
+
+impl ExternWrapper {
+    /// Creates a generator for the given blocking and async wrapper type names.
+    #[must_use]
+    pub fn new(blocking_type: &str, async_type: &str) -> Self {
+        ExternWrapper {
+            blocking_type: blocking_type.to_owned(),
+            async_type: async_type.to_owned(),
+            handle: None,
+            code: None,
+            check: None,
+            error: None,
+            prefix: None,
+            sys: None,
+            settings: Vec::new(),
+        }
+    }
+
+    /// The opaque handle type, e.g. `"CrHandle"`. Required.
+    #[must_use]
+    pub fn handle(mut self, handle: &str) -> Self {
+        self.handle = Some(handle.to_owned());
+        self
+    }
+
+    /// The status code type the functions return, e.g. `"std::os::raw::c_int"`. Required.
+    ///
+    /// Functions returning it go through `check`; other return values are passed through.
+    #[must_use]
+    pub fn code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_owned());
+        self
+    }
+
+    /// The path of the `fn(Code) -> Result<(), Error>` mapping return codes. Required.
+    #[must_use]
+    pub fn check(mut self, check: &str) -> Self {
+        self.check = Some(check.to_owned());
+        self
+    }
+
+    /// The error type returned by `check`. Required.
+    #[must_use]
+    pub fn error(mut self, error: &str) -> Self {
+        self.error = Some(error.to_owned());
+        self
+    }
+
+    /// Only wraps functions starting with `prefix`, and strips it from the method names.
+    #[must_use]
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(prefix.to_owned());
+        self
+    }
+
+    /// The path of the module that includes the bindgen output, e.g. `"crate::sys"`.
+    ///
+    /// Without it, the generated code declares the functions itself.
+    #[must_use]
+    pub fn sys(mut self, sys: &str) -> Self {
+        self.sys = Some(sys.to_owned());
+        self
+    }
+
+    /// Adds a `#[blocking_impl]` setting, e.g. `r#"strategy = "block_in_place""#`.
+    ///
+    /// `state`, `dispatcher`, `handle` and `generate_mock` aren't supported by
+    /// `wrap_extern!`, and make [`generate`](Self::generate) fail.
+    #[must_use]
+    pub fn setting(mut self, setting: &str) -> Self {
+        self.settings.push(setting.to_owned());
+        self
+    }
+
+    /// Returns the `wrap_extern!` invocation for the functions declared in `bindings`.
+    ///
+    /// # Errors
+    ///
+    /// If `bindings` or one of the configured paths and types can't be parsed, if the handle,
+    /// code type, check function or error type is missing, or if a setting isn't supported.
+    pub fn generate(&self, bindings: &str) -> io::Result<String> {
+        let file = syn::parse_file(bindings).map_err(invalid_data)?;
+        let functions = file.items.iter().flat_map(|item| match item {
+            syn::Item::ForeignMod(block) => block.items.iter().collect(),
+            _ => Vec::new(),
+        });
+        let functions = functions.filter(|item| match item {
+            syn::ForeignItem::Fn(function) => self
+                .prefix
+                .as_ref()
+                .is_none_or(|prefix| function.sig.ident.to_string().starts_with(prefix.as_str())),
+            _ => false,
+        });
+
+        let blocking_type = tokens(&self.blocking_type)?;
+        let async_type = tokens(&self.async_type)?;
+        let handle = tokens(required(self.handle.as_deref(), "handle")?)?;
+        let code = tokens(required(self.code.as_deref(), "code")?)?;
+        let check = tokens(required(self.check.as_deref(), "check")?)?;
+        let error = tokens(required(self.error.as_deref(), "error")?)?;
+        let prefix = self
+            .prefix
+            .as_ref()
+            .map(|prefix| quote! { , prefix = #prefix });
+        let sys = match &self.sys {
+            Some(sys) => {
+                let sys = tokens(sys)?;
+                Some(quote! { , sys = #sys })
+            }
+            None => None,
+        };
+        let settings = self
+            .settings
+            .iter()
+            .map(|setting| check_setting(setting).and_then(|()| tokens(setting)))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let invocation = quote! {
+            ::asyncwrap::wrap_extern! {
+                #blocking_type, #async_type, handle = #handle, code = #code, check = #check,
+                error = #error
+                #prefix #sys #(, #settings)*
+                extern "C" {
+                    #(#functions)*
+                }
+            }
+        };
+        Ok(invocation.to_string())
+    }
+
+    /// Reads bindgen output from `bindings` and writes the `wrap_extern!` invocation to `out`.
+    ///
+    /// # Errors
+    ///
+    /// If a file can't be read or written, or [`generate`](Self::generate) fails.
+    pub fn write(&self, bindings: impl AsRef<Path>, out: impl AsRef<Path>) -> io::Result<()> {
+        let bindings = std::fs::read_to_string(bindings)?;
+        std::fs::write(out, self.generate(&bindings)?)
+    }
+}
+
+fn required<'a>(value: Option<&'a str>, name: &str) -> io::Result<&'a str> {
+    value.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("ExternWrapper requires `{name}`"),
+        )
+    })
+}
+
+/// Rejects the settings `wrap_extern!` doesn't support, which would otherwise fail in the
+/// generated file.
+fn check_setting(setting: &str) -> io::Result<()> {
+    let key = setting.split(['=', '(']).next().unwrap_or_default().trim();
+    let is_string = setting
+        .split_once('=')
+        .is_some_and(|(_, value)| value.trim_start().starts_with('"'));
+    let message = match key {
+        "handle" if is_string => {
+            "`wrap_extern!` does not support the runtime `handle` option".to_owned()
+        }
+        "handle" | "code" | "check" | "error" | "prefix" | "sys" => {
+            format!("set `{key}` with `ExternWrapper::{key}` instead of `setting`")
+        }
+        "state" | "dispatcher" | "generate_mock" => {
+            format!("`{key}` is not supported by `wrap_extern!`")
+        }
+        _ => return Ok(()),
+    };
+    Err(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+fn tokens(source: &str) -> io::Result<TokenStream> {
+    source.parse().map_err(invalid_data)
+}
+
+fn invalid_data(err: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
@@ -0,0 +1,110 @@
+//! Result caching for `#[async_wrap(cache(...))]`.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::state::Key;
+
+/// An argument of a cached method, which together with the others identifies a cached result.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` must implement `Hash`, `Eq` and `Clone` to be passed to a method with `cache`",
+    label = "this argument identifies cached results",
+    note = "`#[async_wrap(cache)]` reuses the result of an earlier call with equal arguments"
+)]
+pub trait CacheKey {
+    #[must_use]
+    fn cache_key(&self) -> Key;
+}
+
+impl<T: Hash + Eq + Clone + Send + Sync + 'static> CacheKey for T {
+    fn cache_key(&self) -> Key {
+        Key::new(self)
+    }
+}
+
+/// The return value of a cached method, which every cache hit receives a copy of.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` must implement `Clone` to be returned by a method with `cache`",
+    note = "`#[async_wrap(cache)]` returns a copy of the cached result"
+)]
+pub trait CacheOutput {
+    #[must_use]
+    fn share(&self) -> Self;
+}
+
+impl<T: Clone> CacheOutput for T {
+    fn share(&self) -> Self {
+        self.clone()
+    }
+}
+
+/// The cached results of one method, by arguments.
+pub struct Cache<R> {
+    entries: Mutex<HashMap<Vec<Key>, Entry<R>>>,
+}
+
+struct Entry<R> {
+    value: R,
+    stored: Instant,
+}
+
+impl<R> Default for Cache<R> {
+    fn default() -> Self {
+        Cache {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<R> Cache<R> {
+    fn lock(&self) -> MutexGuard<'_, HashMap<Vec<Key>, Entry<R>>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Discards every cached result.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+impl<R: CacheOutput> Cache<R> {
+    /// The result cached for `key`, unless it is older than `ttl`.
+    pub fn get(&self, key: &[Key], ttl: Option<Duration>) -> Option<R> {
+        let mut entries = self.lock();
+        let entry = entries.get(key)?;
+        if ttl.is_some_and(|ttl| entry.stored.elapsed() >= ttl) {
+            entries.remove(key);
+            return None;
+        }
+        Some(entry.value.share())
+    }
+
+    /// Caches `value` for `key`. When `capacity` results are cached, expired results are
+    /// discarded first, then the oldest.
+    pub fn insert(&self, key: Vec<Key>, value: &R, ttl: Option<Duration>, capacity: usize) {
+        let mut entries = self.lock();
+        if entries.len() >= capacity && !entries.contains_key(&key) {
+            if let Some(ttl) = ttl {
+                entries.retain(|_, entry| entry.stored.elapsed() < ttl);
+            }
+            if entries.len() >= capacity {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.stored)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                value: value.share(),
+                stored: Instant::now(),
+            },
+        );
+    }
+}
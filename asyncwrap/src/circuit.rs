@@ -0,0 +1,237 @@
+//! Circuit breaker state for `#[blocking_impl(..., circuit_breaker(...))]`.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Settings of a circuit breaker, generated from the `circuit_breaker(...)` options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    failure_threshold: u32,
+    open_for: Duration,
+    half_open_probes: u32,
+}
+
+impl CircuitBreakerConfig {
+    /// Opens the circuit after `failure_threshold` consecutive failures, keeps it open for
+    /// `open_for`, then lets `half_open_probes` calls through to decide whether to close it.
+    #[must_use]
+    pub const fn new(failure_threshold: u32, open_for: Duration, half_open_probes: u32) -> Self {
+        CircuitBreakerConfig {
+            failure_threshold,
+            open_for,
+            half_open_probes,
+        }
+    }
+
+    /// Consecutive failures that open the circuit.
+    #[must_use]
+    pub const fn failure_threshold(&self) -> u32 {
+        self.failure_threshold
+    }
+
+    /// How long the circuit stays open before probing again.
+    #[must_use]
+    pub const fn open_for(&self) -> Duration {
+        self.open_for
+    }
+
+    /// Successful probes needed to close a half-open circuit.
+    #[must_use]
+    pub const fn half_open_probes(&self) -> u32 {
+        self.half_open_probes
+    }
+}
+
+/// The observable state of a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CircuitState {
+    /// Calls go through; failures are being counted.
+    Closed,
+    /// Calls fail fast with `AsyncWrapError::CircuitOpen`.
+    Open,
+    /// A limited number of probe calls go through to test whether the backend recovered.
+    HalfOpen,
+}
+
+/// Shared failure-tracking state, stored as a field of the async wrapper struct.
+///
+/// The breaker itself holds no settings: generated methods pass the
+/// [`CircuitBreakerConfig`] from the `#[blocking_impl]` attribute on every call.
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    half_open: Option<HalfOpen>,
+    /// How many half-open phases have started, kept across resets so probe permits of an
+    /// earlier phase are never mistaken for the current one's.
+    half_opens: u64,
+}
+
+#[derive(Debug)]
+struct HalfOpen {
+    generation: u64,
+    in_flight: u32,
+    successes: u32,
+}
+
+impl CircuitBreaker {
+    /// Creates a closed circuit breaker.
+    #[must_use]
+    pub fn new() -> Self {
+        CircuitBreaker::default()
+    }
+
+    /// Returns the current state, moving an expired open circuit to half-open.
+    pub fn state(&self, config: &CircuitBreakerConfig) -> CircuitState {
+        let mut state = self.lock();
+        state.expire_open(config);
+        if state.half_open.is_some() {
+            CircuitState::HalfOpen
+        } else if state.opened_at.is_some() {
+            CircuitState::Open
+        } else {
+            CircuitState::Closed
+        }
+    }
+
+    /// Closes the circuit and forgets all recorded failures.
+    pub fn reset(&self) {
+        self.lock().close();
+    }
+
+    /// Asks to make a call, returning `None` if the circuit rejects it.
+    ///
+    /// The returned permit must be completed with [`CircuitPermit::record`]; dropping it
+    /// without a result (e.g. when the calling future is cancelled) records nothing.
+    pub fn try_acquire<'a>(
+        &'a self,
+        config: &'a CircuitBreakerConfig,
+    ) -> Option<CircuitPermit<'a>> {
+        let mut state = self.lock();
+        state.expire_open(config);
+
+        let BreakerState {
+            opened_at,
+            half_open,
+            ..
+        } = &mut *state;
+        let probe = match half_open {
+            Some(half_open)
+                if half_open.in_flight + half_open.successes >= config.half_open_probes =>
+            {
+                return None
+            }
+            Some(half_open) => {
+                half_open.in_flight += 1;
+                Some(half_open.generation)
+            }
+            None if opened_at.is_some() => return None,
+            None => None,
+        };
+
+        Some(CircuitPermit {
+            breaker: self,
+            config,
+            probe,
+            recorded: false,
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BreakerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl BreakerState {
+    fn expire_open(&mut self, config: &CircuitBreakerConfig) {
+        if self.half_open.is_none()
+            && self
+                .opened_at
+                .is_some_and(|opened_at| opened_at.elapsed() >= config.open_for)
+        {
+            self.half_opens += 1;
+            self.half_open = Some(HalfOpen {
+                generation: self.half_opens,
+                in_flight: 0,
+                successes: 0,
+            });
+        }
+    }
+
+    fn open(&mut self) {
+        self.opened_at = Some(Instant::now());
+        self.half_open = None;
+    }
+
+    fn close(&mut self) {
+        *self = BreakerState {
+            half_opens: self.half_opens,
+            ..BreakerState::default()
+        };
+    }
+
+    /// The half-open phase that issued a probe permit of `generation`, unless it already
+    /// ended.
+    fn probing(&mut self, generation: u64) -> Option<&mut HalfOpen> {
+        self.half_open
+            .as_mut()
+            .filter(|half_open| half_open.generation == generation)
+    }
+}
+
+/// Permission to make one call through a [`CircuitBreaker`].
+#[derive(Debug)]
+#[must_use = "a permit must be completed with `record`"]
+pub struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    config: &'a CircuitBreakerConfig,
+    /// The half-open phase this probe belongs to, for probe permits.
+    probe: Option<u64>,
+    recorded: bool,
+}
+
+impl CircuitPermit<'_> {
+    /// Records the outcome of the call. The outcome of a probe is ignored once its half-open
+    /// phase has ended.
+    pub fn record(mut self, success: bool) {
+        self.recorded = true;
+        let mut state = self.breaker.lock();
+
+        if let Some(generation) = self.probe {
+            let Some(half_open) = state.probing(generation) else {
+                return;
+            };
+            half_open.in_flight -= 1;
+            if !success {
+                state.open();
+                return;
+            }
+            half_open.successes += 1;
+            if half_open.successes >= self.config.half_open_probes {
+                state.close();
+            }
+        } else if success {
+            state.consecutive_failures = 0;
+        } else if state.opened_at.is_none() {
+            state.consecutive_failures += 1;
+            if state.consecutive_failures >= self.config.failure_threshold {
+                state.open();
+            }
+        }
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if let (Some(generation), false) = (self.probe, self.recorded) {
+            if let Some(half_open) = self.breaker.lock().probing(generation) {
+                half_open.in_flight -= 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! Single-flight execution for `#[async_wrap(coalesce)]`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use std::task::{Context, Poll, Wake, Waker};
+
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+use crate::observe::CallObservation;
+use crate::state::Key;
+use crate::{CallSite, TaskFailure};
+
+/// An argument of a coalesced method, which together with the others identifies identical calls.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` must implement `Hash`, `Eq` and `Clone` to be passed to a method with `coalesce`",
+    label = "this argument identifies identical calls",
+    note = "`#[async_wrap(coalesce)]` shares one blocking call between calls with equal arguments"
+)]
+pub trait CoalesceKey {
+    #[must_use]
+    fn coalesce_key(&self) -> Key;
+}
+
+impl<T: Hash + Eq + Clone + Send + Sync + 'static> CoalesceKey for T {
+    fn coalesce_key(&self) -> Key {
+        Key::new(self)
+    }
+}
+
+/// The return value of a coalesced method, which every waiting caller receives a copy of.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` must implement `Clone` to be returned by a method with `coalesce`",
+    note = "`#[async_wrap(coalesce)]` gives every caller a copy of the shared result"
+)]
+pub trait CoalesceOutput {
+    #[must_use]
+    fn share(&self) -> Self;
+}
+
+impl<T: Clone> CoalesceOutput for T {
+    fn share(&self) -> Self {
+        self.clone()
+    }
+}
+
+/// The in-flight calls of one coalesced method, by arguments.
+pub struct Flights<R> {
+    flights: Mutex<HashMap<Vec<Key>, Weak<Flight<R>>>>,
+}
+
+impl<R> Default for Flights<R> {
+    fn default() -> Self {
+        Flights {
+            flights: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<R: CoalesceOutput + Send + 'static> Flights<R> {
+    /// Joins the in-flight call with the same `key`, or starts one: `start` is called before
+    /// the blocking call is queued, and returns its observation, if any, and the call to run
+    /// on the blocking pool of `runtime`.
+    pub fn run<C>(
+        self: &Arc<Self>,
+        runtime: &Handle,
+        key: Vec<Key>,
+        call_site: CallSite,
+        start: impl FnOnce() -> (Option<CallObservation>, C),
+    ) -> Joined<R>
+    where
+        C: FnOnce() -> R + Send + 'static,
+    {
+        let mut flights = self.flights.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(flight) = flights.get(&key).and_then(Weak::upgrade) {
+            return Joined { flight };
+        }
+
+        let (observation, call) = start();
+        let flight = Arc::new(Flight {
+            key: key.clone(),
+            flights: Arc::downgrade(self),
+            call_site,
+            observation,
+            state: Mutex::new(FlightState {
+                handle: Some(runtime.spawn_blocking(call)),
+                result: None,
+            }),
+            waiters: Arc::new(Waiters::default()),
+        });
+        flights.insert(key, Arc::downgrade(&flight));
+        Joined { flight }
+    }
+}
+
+struct Flight<R> {
+    key: Vec<Key>,
+    flights: Weak<Flights<R>>,
+    call_site: CallSite,
+    observation: Option<CallObservation>,
+    state: Mutex<FlightState<R>>,
+    waiters: Arc<Waiters>,
+}
+
+struct FlightState<R> {
+    handle: Option<JoinHandle<R>>,
+    result: Option<Result<R, TaskFailure>>,
+}
+
+impl<R> Flight<R> {
+    fn lock(&self) -> MutexGuard<'_, FlightState<R>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Removes this flight from the in-flight calls, so later calls start a new one.
+    fn land(&self) {
+        let Some(flights) = self.flights.upgrade() else {
+            return;
+        };
+        let mut flights = flights
+            .flights
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let is_self = flights
+            .get(&self.key)
+            .is_some_and(|flight| std::ptr::eq(flight.as_ptr(), self));
+        if is_self {
+            flights.remove(&self.key);
+        }
+    }
+}
+
+impl<R> Drop for Flight<R> {
+    // Runs once every waiter has dropped; the blocking call is detached, as when an
+    // uncoalesced call is dropped.
+    fn drop(&mut self) {
+        self.land();
+    }
+}
+
+/// Wakes every caller waiting on a flight when its blocking call completes.
+#[derive(Default)]
+struct Waiters {
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Waiters {
+    fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap_or_else(PoisonError::into_inner);
+        if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+}
+
+impl Wake for Waiters {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let wakers =
+            std::mem::take(&mut *self.wakers.lock().unwrap_or_else(PoisonError::into_inner));
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// A caller waiting on a coalesced call.
+pub struct Joined<R> {
+    flight: Arc<Flight<R>>,
+}
+
+impl<R: CoalesceOutput> Future for Joined<R> {
+    type Output = Result<R, TaskFailure>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let flight = &*self.flight;
+        let mut state = flight.lock();
+        if let Some(result) = &state.result {
+            return Poll::Ready(share(result));
+        }
+
+        flight.waiters.register(cx.waker());
+        let waker = Waker::from(Arc::clone(&flight.waiters));
+        let Some(handle) = &mut state.handle else {
+            unreachable!("a flight has a handle until it has a result");
+        };
+        let Poll::Ready(joined) = Pin::new(handle).poll(&mut Context::from_waker(&waker)) else {
+            return Poll::Pending;
+        };
+
+        let result = joined.map_err(|err| {
+            let failure = TaskFailure::from_join_error(err, flight.call_site);
+            if let Some(observation) = &flight.observation {
+                observation.report_failure(&failure);
+            }
+            failure
+        });
+        state.handle = None;
+        state.result = Some(share(&result));
+        drop(state);
+        flight.land();
+        flight.waiters.wake_by_ref();
+        Poll::Ready(result)
+    }
+}
+
+fn share<R: CoalesceOutput>(result: &Result<R, TaskFailure>) -> Result<R, TaskFailure> {
+    match result {
+        Ok(value) => Ok(value.share()),
+        Err(failure) => Err(failure.clone()),
+    }
+}
@@ -0,0 +1,160 @@
+//! Priority-ordered admission for `#[blocking_impl(..., dispatcher = "field")]`.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use tokio::sync::oneshot;
+
+/// The priority class of a blocking call, set with `#[async_wrap(priority = "...")]` or per
+/// call with the generated `<method>_with_priority`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Started before any waiting `Normal` or `Low` call.
+    High,
+    /// The default.
+    #[default]
+    Normal,
+    /// Started only when no `High` or `Normal` call is waiting.
+    Low,
+}
+
+/// Limits how many blocking calls run at once, starting waiting calls by [`Priority`].
+///
+/// Store a `Dispatcher` in the async wrapper's field named by
+/// `#[blocking_impl(..., dispatcher = "field")]`. Clones share the same permits, so one
+/// dispatcher can be shared by several wrappers. Calls of the same priority start in the order
+/// they arrived.
+#[derive(Clone)]
+pub struct Dispatcher {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    permits: usize,
+    state: Mutex<State>,
+}
+
+struct State {
+    running: usize,
+    /// Waiting calls by priority, highest first.
+    waiting: [VecDeque<oneshot::Sender<DispatchPermit>>; 3],
+}
+
+impl Dispatcher {
+    /// A dispatcher running at most `permits` blocking calls at once.
+    ///
+    /// # Panics
+    ///
+    /// If `permits` is zero.
+    #[must_use]
+    pub fn new(permits: usize) -> Self {
+        assert!(permits > 0, "a dispatcher needs at least one permit");
+        Dispatcher {
+            inner: Arc::new(Inner {
+                permits,
+                state: Mutex::new(State {
+                    running: 0,
+                    waiting: Default::default(),
+                }),
+            }),
+        }
+    }
+
+    /// The maximum number of blocking calls running at once.
+    #[must_use]
+    pub fn permits(&self) -> usize {
+        self.inner.permits
+    }
+
+    /// The number of blocking calls running now.
+    #[must_use]
+    pub fn running(&self) -> usize {
+        self.inner.lock().running
+    }
+
+    /// The number of calls waiting for a permit.
+    #[must_use]
+    pub fn waiting(&self) -> usize {
+        self.inner.lock().waiting.iter().map(VecDeque::len).sum()
+    }
+
+    /// Waits for a permit to run a blocking call, after every waiting call of a higher
+    /// priority and the earlier ones of the same priority.
+    pub async fn acquire(&self, priority: Priority) -> DispatchPermit {
+        let receiver = {
+            let mut state = self.inner.lock();
+            // Forget callers that stopped waiting, so they don't hold up free permits.
+            for queue in &mut state.waiting {
+                queue.retain(|waiter| !waiter.is_closed());
+            }
+            if state.running < self.inner.permits && state.waiting.iter().all(VecDeque::is_empty) {
+                state.running += 1;
+                return DispatchPermit {
+                    inner: Some(Arc::clone(&self.inner)),
+                };
+            }
+            let (sender, receiver) = oneshot::channel();
+            state.waiting[priority as usize].push_back(sender);
+            receiver
+        };
+        receiver
+            .await
+            .unwrap_or_else(|_| unreachable!("waiters are only dropped after they are served"))
+    }
+}
+
+impl fmt::Debug for Dispatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dispatcher")
+            .field("permits", &self.permits())
+            .field("running", &self.running())
+            .field("waiting", &self.waiting())
+            .finish()
+    }
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Hands a released permit to the next waiting call, or returns it.
+    fn release(self: &Arc<Self>) {
+        let mut state = self.lock();
+        loop {
+            let Some(waiter) = state.waiting.iter_mut().find_map(VecDeque::pop_front) else {
+                state.running -= 1;
+                return;
+            };
+            let permit = DispatchPermit {
+                inner: Some(Arc::clone(self)),
+            };
+            match waiter.send(permit) {
+                Ok(()) => return,
+                // The caller stopped waiting; the permit goes back without being released.
+                Err(mut permit) => permit.inner = None,
+            }
+        }
+    }
+}
+
+/// Permission to run one blocking call, released when dropped.
+#[must_use = "the permit is released when dropped"]
+pub struct DispatchPermit {
+    inner: Option<Arc<Inner>>,
+}
+
+impl Drop for DispatchPermit {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            inner.release();
+        }
+    }
+}
+
+impl fmt::Debug for DispatchPermit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DispatchPermit").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,55 @@
+//! Runtime support for the blocking facades generated by `#[async_impl]`.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::runtime::{Handle, Runtime};
+
+use crate::{AsyncWrapError, CallSite};
+
+/// A runtime that can drive a future to completion from synchronous code.
+///
+/// The runtime field of a type generated with `#[async_impl]` must implement this trait. It is
+/// implemented for an owned [`Runtime`], a [`Handle`] to a runtime running elsewhere, and
+/// `Arc`s of either.
+pub trait BlockOn {
+    /// Runs `future` to completion, blocking the current thread.
+    fn block_on<F: Future>(&self, future: F) -> F::Output;
+}
+
+impl BlockOn for Runtime {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        Runtime::block_on(self, future)
+    }
+}
+
+impl BlockOn for Handle {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        Handle::block_on(self, future)
+    }
+}
+
+impl<T: BlockOn + ?Sized> BlockOn for Arc<T> {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        T::block_on(self, future)
+    }
+}
+
+/// Rejects blocking calls made from a thread with a runtime context.
+///
+/// `block_on` panics on a runtime worker thread, and blocking a worker could deadlock the
+/// future being waited on, so the facade returns [`AsyncWrapError::InsideRuntime`] instead.
+///
+/// Tokio doesn't expose whether a thread is a worker, only whether it has a runtime context, so
+/// `spawn_blocking` threads are rejected too, even though `block_on` would work there.
+///
+/// # Errors
+///
+/// If the current thread is inside a Tokio runtime context, including a `spawn_blocking`
+/// thread.
+pub fn ensure_outside_runtime<E>(call_site: CallSite) -> Result<(), AsyncWrapError<E>> {
+    match Handle::try_current() {
+        Ok(_) => Err(AsyncWrapError::InsideRuntime(call_site)),
+        Err(_) => Ok(()),
+    }
+}
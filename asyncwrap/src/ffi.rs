@@ -0,0 +1,42 @@
+//! Support for the wrappers generated by `wrap_extern!`.
+
+#![allow(unsafe_code)]
+
+use std::fmt;
+
+/// A raw C handle that can be moved and shared between threads.
+///
+/// Raw pointers are neither `Send` nor `Sync`, so a type holding one can't be passed to
+/// `spawn_blocking`. Wrapping the pointer records the caller's promise that the C library
+/// allows the handle to be used from other threads.
+pub struct SendPtr<T>(*mut T);
+
+impl<T> SendPtr<T> {
+    /// Wraps a raw handle.
+    ///
+    /// # Safety
+    ///
+    /// The C library must allow `ptr` to be used from any thread, including from several
+    /// threads at once, for as long as the `SendPtr` or a type holding it is alive.
+    #[must_use]
+    pub const unsafe fn new(ptr: *mut T) -> Self {
+        SendPtr(ptr)
+    }
+
+    /// Returns the raw handle.
+    #[must_use]
+    pub const fn as_ptr(&self) -> *mut T {
+        self.0
+    }
+}
+
+// SAFETY: guaranteed by the caller of `SendPtr::new`.
+unsafe impl<T> Send for SendPtr<T> {}
+// SAFETY: guaranteed by the caller of `SendPtr::new`.
+unsafe impl<T> Sync for SendPtr<T> {}
+
+impl<T> fmt::Debug for SendPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SendPtr").field(&self.0).finish()
+    }
+}
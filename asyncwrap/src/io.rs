@@ -0,0 +1,315 @@
+//! Async adapters for blocking [`Read`] and [`Write`] implementations.
+//!
+//! `Read::read` and `Write::write` take `&mut self` and borrowed buffers, so they can't be
+//! wrapped with `#[async_wrap]`. [`BlockingReader`] and [`BlockingWriter`] instead own the
+//! blocking value and an internal buffer, move both onto the blocking pool for each call, and
+//! implement tokio's [`AsyncRead`], [`AsyncBufRead`] and [`AsyncWrite`] (and the `futures-io`
+//! traits with the `futures-io` feature).
+
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, ReadBuf};
+use tokio::task::JoinHandle;
+
+use crate::TaskFailure;
+
+/// The default buffer size, matching `std::io::BufReader`.
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+type Operation<T> = JoinHandle<(T, Vec<u8>, io::Result<()>)>;
+
+/// A blocking value and its buffer, either available or moved into a blocking-pool task.
+struct Blocking<T> {
+    inner: Option<T>,
+    buf: Vec<u8>,
+    pending: Option<Operation<T>>,
+}
+
+impl<T: Send + 'static> Blocking<T> {
+    fn new(inner: T, capacity: usize) -> Self {
+        Blocking {
+            inner: Some(inner),
+            buf: Vec::with_capacity(capacity),
+            pending: None,
+        }
+    }
+
+    /// Runs `op` on the blocking pool with the value and the buffer.
+    fn spawn<F>(&mut self, op: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut T, &mut Vec<u8>) -> io::Result<()> + Send + 'static,
+    {
+        let Some(mut inner) = self.inner.take() else {
+            return Err(io::Error::other(
+                "the blocking I/O object was lost when a previous call panicked",
+            ));
+        };
+        let mut buf = std::mem::take(&mut self.buf);
+        self.pending = Some(tokio::task::spawn_blocking(move || {
+            let result = op(&mut inner, &mut buf);
+            (inner, buf, result)
+        }));
+        Ok(())
+    }
+
+    /// Waits for the running operation, if any, and returns its result.
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let Some(pending) = &mut self.pending else {
+            return Poll::Ready(Ok(()));
+        };
+        let joined = ready!(Pin::new(pending).poll(cx));
+        self.pending = None;
+
+        match joined {
+            Ok((inner, buf, result)) => {
+                self.inner = Some(inner);
+                self.buf = buf;
+                Poll::Ready(result)
+            }
+            Err(err) => Poll::Ready(Err(io::Error::other(TaskFailure::from(err)))),
+        }
+    }
+}
+
+/// Adapts a blocking [`Read`] into [`AsyncRead`] and [`AsyncBufRead`].
+///
+/// Each read fills an internal buffer with one `Read::read` call on the blocking pool. Reads
+/// interrupted with `ErrorKind::Interrupted` are retried.
+pub struct BlockingReader<R> {
+    state: Blocking<R>,
+    capacity: usize,
+    pos: usize,
+}
+
+impl<R: Read + Send + 'static> BlockingReader<R> {
+    /// Wraps `inner` with an 8 KiB buffer.
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Wraps `inner`, reading up to `capacity` bytes per blocking call.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is zero.
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        assert!(capacity > 0, "BlockingReader capacity must be non-zero");
+        BlockingReader {
+            state: Blocking::new(inner, capacity),
+            capacity,
+            pos: 0,
+        }
+    }
+
+    fn fill_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        while self.pos >= self.state.buf.len() {
+            if self.state.pending.is_some() {
+                ready!(self.state.poll_pending(cx))?;
+                self.pos = 0;
+                if self.state.buf.is_empty() {
+                    break;
+                }
+            } else {
+                let capacity = self.capacity;
+                self.state.spawn(move |inner, buf| {
+                    buf.resize(capacity, 0);
+                    loop {
+                        match inner.read(buf) {
+                            Ok(n) => {
+                                buf.truncate(n);
+                                return Ok(());
+                            }
+                            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                            Err(err) => {
+                                buf.clear();
+                                return Err(err);
+                            }
+                        }
+                    }
+                })?;
+            }
+        }
+        Poll::Ready(Ok(&self.state.buf[self.pos..]))
+    }
+
+    fn consume_buf(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.state.buf.len());
+    }
+
+    fn read_into(&mut self, cx: &mut Context<'_>, dst: &mut [u8]) -> Poll<io::Result<usize>> {
+        if dst.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let available = ready!(self.fill_buf(cx))?;
+        let n = available.len().min(dst.len());
+        dst[..n].copy_from_slice(&available[..n]);
+        self.consume_buf(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+// The blocking value is only ever moved, never pinned.
+impl<R> Unpin for BlockingReader<R> {}
+
+impl<R: Read + Send + 'static> AsyncRead for BlockingReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let n = ready!(self.get_mut().read_into(cx, buf.initialize_unfilled()))?;
+        buf.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: Read + Send + 'static> AsyncBufRead for BlockingReader<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.get_mut().fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().consume_buf(amt);
+    }
+}
+
+/// Adapts a blocking [`Write`] into [`AsyncWrite`].
+///
+/// Writes are collected in an internal buffer, which is written with `Write::write_all` on the
+/// blocking pool when it is full and on flush. Flushing also calls `Write::flush`, and shutdown
+/// flushes. Data still buffered when the writer is dropped is discarded, so flush or shut down
+/// the writer first.
+pub struct BlockingWriter<W> {
+    state: Blocking<W>,
+    capacity: usize,
+    flushing: bool,
+}
+
+impl<W: Write + Send + 'static> BlockingWriter<W> {
+    /// Wraps `inner` with an 8 KiB buffer.
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Wraps `inner`, buffering up to `capacity` bytes between blocking calls.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is zero.
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        assert!(capacity > 0, "BlockingWriter capacity must be non-zero");
+        BlockingWriter {
+            state: Blocking::new(inner, capacity),
+            capacity,
+            flushing: false,
+        }
+    }
+
+    fn write_buf(&mut self, cx: &mut Context<'_>, data: &[u8]) -> Poll<io::Result<usize>> {
+        loop {
+            let result = ready!(self.state.poll_pending(cx));
+            self.flushing = false;
+            result?;
+
+            if data.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+            let space = self.capacity - self.state.buf.len();
+            if space > 0 {
+                let n = space.min(data.len());
+                self.state.buf.extend_from_slice(&data[..n]);
+                return Poll::Ready(Ok(n));
+            }
+
+            self.state.spawn(|inner, buf| {
+                let result = inner.write_all(buf);
+                buf.clear();
+                result
+            })?;
+        }
+    }
+
+    fn flush_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            let result = ready!(self.state.poll_pending(cx));
+            let was_flushing = std::mem::take(&mut self.flushing);
+            result?;
+            if was_flushing {
+                return Poll::Ready(Ok(()));
+            }
+
+            self.state.spawn(|inner, buf| {
+                let result = inner.write_all(buf);
+                buf.clear();
+                result?;
+                inner.flush()
+            })?;
+            self.flushing = true;
+        }
+    }
+}
+
+// The blocking value is only ever moved, never pinned.
+impl<W> Unpin for BlockingWriter<W> {}
+
+impl<W: Write + Send + 'static> AsyncWrite for BlockingWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().write_buf(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().flush_buf(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().flush_buf(cx)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<R: Read + Send + 'static> futures_io::AsyncRead for BlockingReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().read_into(cx, buf)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<R: Read + Send + 'static> futures_io::AsyncBufRead for BlockingReader<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.get_mut().fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().consume_buf(amt);
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<W: Write + Send + 'static> futures_io::AsyncWrite for BlockingWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().write_buf(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().flush_buf(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().flush_buf(cx)
+    }
+}
@@ -6,6 +6,9 @@
 //! - `#[blocking_impl(AsyncType)]` - Processes an impl block and generates async wrappers
 //! - `#[async_wrap]` - Marks individual methods for async wrapper generation
 //!
+//! `#[async_impl(BlockingType)]` and `#[blocking_wrap]` do the opposite, generating blocking
+//! methods that run async methods to completion on a stored runtime.
+//!
 //! # Example
 //!
 //! ```ignore
@@ -27,18 +30,63 @@
 //! }
 //! ```
 
-pub use asyncwrap_macros::{async_wrap, blocking_impl};
+mod batch;
+#[cfg(feature = "build")]
+pub mod build;
+mod cache;
+mod circuit;
+mod coalesce;
+mod dispatch;
+mod facade;
+pub mod ffi;
+pub mod io;
+#[cfg(feature = "mock")]
+pub mod mock;
+mod observe;
+mod pool;
+mod progress;
+mod retry;
+mod slow;
+mod state;
+mod subscribe;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod thread_hooks;
+
+pub use asyncwrap_macros::{
+    async_impl, async_wrap, blocking_impl, blocking_mod, blocking_wrap, wrap_extern, wrap_foreign,
+};
+pub use circuit::{CircuitBreaker, CircuitBreakerConfig, CircuitPermit, CircuitState};
+pub use dispatch::{DispatchPermit, Dispatcher, Priority};
+pub use facade::BlockOn;
+pub use observe::{CallOutcome, CallTiming, WrapObserver};
+pub use pool::{PoolBuilder, PoolError};
+pub use progress::{Progress, ProgressState, WithProgress};
+pub use retry::{Backoff, RetryPolicy};
+pub use slow::{log_slow_call, SlowCall};
+pub use state::WrapState;
+pub use subscribe::{Overflow, Subscription};
+
+use std::any::Any;
+use std::convert::Infallible;
+use std::sync::Mutex;
 
 /// Error type for async wrapper operations.
 ///
 /// This wraps the original error type from the blocking method and adds
-/// the possibility of a task failure (panic or cancellation).
+/// the possibility of a task failure (panic or cancellation). New variants may be added in
+/// minor releases, so matches need a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum AsyncWrapError<E> {
     /// The underlying blocking operation failed
     Inner(E),
-    /// The spawned task was cancelled or panicked
-    TaskFailed(tokio::task::JoinError),
+    /// The blocking call panicked or its task was cancelled
+    TaskFailed(TaskFailure),
+    /// The call was rejected without running because the circuit breaker is open
+    CircuitOpen(CallSite),
+    /// A blocking facade generated by `#[async_impl]` was called from inside an async runtime
+    InsideRuntime(CallSite),
 }
 
 /// Result type alias for methods that return `Result<T, E>`.
@@ -58,9 +106,176 @@ impl<T, E> ResultType for std::result::Result<T, E> {
     type Err = E;
 }
 
+impl<E> AsyncWrapError<E> {
+    /// Returns the blocking method's error, or `None` for any non-`Inner` variant.
+    pub fn into_inner(self) -> Option<E> {
+        match self {
+            AsyncWrapError::Inner(e) => Some(e),
+            AsyncWrapError::TaskFailed(_)
+            | AsyncWrapError::CircuitOpen(_)
+            | AsyncWrapError::InsideRuntime(_) => None,
+        }
+    }
+
+    /// Returns a reference to the blocking method's error, or `None` for any non-`Inner` variant.
+    pub fn inner(&self) -> Option<&E> {
+        match self {
+            AsyncWrapError::Inner(e) => Some(e),
+            AsyncWrapError::TaskFailed(_)
+            | AsyncWrapError::CircuitOpen(_)
+            | AsyncWrapError::InsideRuntime(_) => None,
+        }
+    }
+
+    /// Returns a mutable reference to the blocking method's error, or `None` for any
+    /// non-`Inner` variant.
+    pub fn inner_mut(&mut self) -> Option<&mut E> {
+        match self {
+            AsyncWrapError::Inner(e) => Some(e),
+            AsyncWrapError::TaskFailed(_)
+            | AsyncWrapError::CircuitOpen(_)
+            | AsyncWrapError::InsideRuntime(_) => None,
+        }
+    }
+
+    /// Maps the blocking method's error with `f`, leaving the other variants untouched.
+    pub fn map_inner<F>(self, f: impl FnOnce(E) -> F) -> AsyncWrapError<F> {
+        match self {
+            AsyncWrapError::Inner(e) => AsyncWrapError::Inner(f(e)),
+            AsyncWrapError::TaskFailed(failure) => AsyncWrapError::TaskFailed(failure),
+            AsyncWrapError::CircuitOpen(call_site) => AsyncWrapError::CircuitOpen(call_site),
+            AsyncWrapError::InsideRuntime(call_site) => AsyncWrapError::InsideRuntime(call_site),
+        }
+    }
+
+    /// Returns `true` if the blocking call panicked.
+    pub fn is_panic(&self) -> bool {
+        matches!(self, AsyncWrapError::TaskFailed(failure) if failure.is_panic())
+    }
+
+    /// Returns `true` if the blocking task was cancelled before it completed.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, AsyncWrapError::TaskFailed(failure) if failure.is_cancelled())
+    }
+
+    /// Returns the panic message, if the blocking call panicked with a string payload.
+    pub fn panic_message(&self) -> Option<&str> {
+        match self {
+            AsyncWrapError::TaskFailed(failure) => failure.panic_message(),
+            AsyncWrapError::Inner(_)
+            | AsyncWrapError::CircuitOpen(_)
+            | AsyncWrapError::InsideRuntime(_) => None,
+        }
+    }
+
+    /// Consumes the error, returning the panic payload if the blocking call panicked.
+    pub fn into_panic(self) -> Option<Box<dyn Any + Send + 'static>> {
+        match self {
+            AsyncWrapError::TaskFailed(failure) => failure.into_panic(),
+            AsyncWrapError::Inner(_)
+            | AsyncWrapError::CircuitOpen(_)
+            | AsyncWrapError::InsideRuntime(_) => None,
+        }
+    }
+
+    /// Returns `true` if the call was rejected by an open circuit breaker.
+    pub fn is_circuit_open(&self) -> bool {
+        matches!(self, AsyncWrapError::CircuitOpen(_))
+    }
+
+    /// Returns `true` if a blocking facade was called from inside an async runtime.
+    pub fn is_inside_runtime(&self) -> bool {
+        matches!(self, AsyncWrapError::InsideRuntime(_))
+    }
+
+    /// Returns the generated method that failed, unless this is the blocking method's own error.
+    pub fn call_site(&self) -> Option<CallSite> {
+        match self {
+            AsyncWrapError::TaskFailed(failure) => failure.call_site(),
+            AsyncWrapError::CircuitOpen(call_site) | AsyncWrapError::InsideRuntime(call_site) => {
+                Some(*call_site)
+            }
+            AsyncWrapError::Inner(_) => None,
+        }
+    }
+}
+
+impl<E> AsyncWrapError<AsyncWrapError<E>> {
+    /// Collapses a nested error, e.g. from a wrapped method that itself awaits a wrapped call.
+    pub fn flatten(self) -> AsyncWrapError<E> {
+        match self {
+            AsyncWrapError::Inner(inner) => inner,
+            AsyncWrapError::TaskFailed(failure) => AsyncWrapError::TaskFailed(failure),
+            AsyncWrapError::CircuitOpen(call_site) => AsyncWrapError::CircuitOpen(call_site),
+            AsyncWrapError::InsideRuntime(call_site) => AsyncWrapError::InsideRuntime(call_site),
+        }
+    }
+}
+
+#[cfg(feature = "anyhow")]
+impl<E: Into<anyhow::Error>> AsyncWrapError<E> {
+    /// Converts into an [`anyhow::Error`], passing the blocking method's error through unwrapped.
+    ///
+    /// Unlike `?`, this keeps `downcast_ref::<E>()` working on the result and also accepts
+    /// `AsyncWrapError<anyhow::Error>`.
+    pub fn into_anyhow(self) -> anyhow::Error {
+        match self {
+            AsyncWrapError::Inner(e) => e.into(),
+            AsyncWrapError::TaskFailed(failure) => anyhow::Error::new(failure),
+            AsyncWrapError::CircuitOpen(call_site) => {
+                anyhow::Error::new(AsyncWrapError::<Infallible>::CircuitOpen(call_site))
+            }
+            AsyncWrapError::InsideRuntime(call_site) => {
+                anyhow::Error::new(AsyncWrapError::<Infallible>::InsideRuntime(call_site))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "eyre")]
+impl<E: Into<eyre::Report>> AsyncWrapError<E> {
+    /// Converts into an [`eyre::Report`], passing the blocking method's error through unwrapped.
+    ///
+    /// Unlike `?`, this keeps `downcast_ref::<E>()` working on the result and also accepts
+    /// `AsyncWrapError<eyre::Report>`.
+    pub fn into_report(self) -> eyre::Report {
+        match self {
+            AsyncWrapError::Inner(e) => e.into(),
+            AsyncWrapError::TaskFailed(failure) => eyre::Report::new(failure),
+            AsyncWrapError::CircuitOpen(call_site) => {
+                eyre::Report::new(AsyncWrapError::<Infallible>::CircuitOpen(call_site))
+            }
+            AsyncWrapError::InsideRuntime(call_site) => {
+                eyre::Report::new(AsyncWrapError::<Infallible>::InsideRuntime(call_site))
+            }
+        }
+    }
+}
+
+impl From<AsyncWrapError<std::io::Error>> for std::io::Error {
+    fn from(err: AsyncWrapError<std::io::Error>) -> Self {
+        match err {
+            AsyncWrapError::Inner(e) => e,
+            AsyncWrapError::TaskFailed(failure) => std::io::Error::other(failure),
+            AsyncWrapError::CircuitOpen(call_site) => {
+                std::io::Error::other(AsyncWrapError::<Infallible>::CircuitOpen(call_site))
+            }
+            AsyncWrapError::InsideRuntime(call_site) => {
+                std::io::Error::other(AsyncWrapError::<Infallible>::InsideRuntime(call_site))
+            }
+        }
+    }
+}
+
 impl<E> From<tokio::task::JoinError> for AsyncWrapError<E> {
     fn from(err: tokio::task::JoinError) -> Self {
-        AsyncWrapError::TaskFailed(err)
+        AsyncWrapError::TaskFailed(TaskFailure::from(err))
+    }
+}
+
+impl<E> From<TaskFailure> for AsyncWrapError<E> {
+    fn from(failure: TaskFailure) -> Self {
+        AsyncWrapError::TaskFailed(failure)
     }
 }
 
@@ -69,6 +284,14 @@ impl<E: std::fmt::Display> std::fmt::Display for AsyncWrapError<E> {
         match self {
             AsyncWrapError::Inner(e) => write!(f, "{e}"),
             AsyncWrapError::TaskFailed(e) => write!(f, "async task failed: {e}"),
+            AsyncWrapError::CircuitOpen(call_site) => {
+                write!(f, "`{call_site}` rejected: circuit breaker is open")
+            }
+            AsyncWrapError::InsideRuntime(call_site) => write!(
+                f,
+                "`{call_site}` cannot block inside an async runtime; \
+                 await the async method instead"
+            ),
         }
     }
 }
@@ -78,6 +301,256 @@ impl<E: std::error::Error + 'static> std::error::Error for AsyncWrapError<E> {
         match self {
             AsyncWrapError::Inner(e) => Some(e),
             AsyncWrapError::TaskFailed(e) => Some(e),
+            AsyncWrapError::CircuitOpen(_) | AsyncWrapError::InsideRuntime(_) => None,
+        }
+    }
+}
+
+/// Identifies a generated async method: the wrapper type and the method name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallSite {
+    type_name: &'static str,
+    method: &'static str,
+}
+
+impl CallSite {
+    /// Creates a call site for `type_name::method`.
+    #[must_use]
+    pub const fn new(type_name: &'static str, method: &'static str) -> Self {
+        CallSite { type_name, method }
+    }
+
+    /// The name of the async wrapper type, e.g. `"AsyncClient"`.
+    #[must_use]
+    pub const fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// The name of the wrapped method, e.g. `"fetch"`.
+    #[must_use]
+    pub const fn method(&self) -> &'static str {
+        self.method
+    }
+}
+
+impl std::fmt::Display for CallSite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}::{}", self.type_name, self.method)
+    }
+}
+
+/// A blocking call that panicked or whose task was cancelled.
+///
+/// Panic payloads are taken out of the `JoinError` eagerly so the message can be inspected
+/// by reference, and every failure produced by generated code records its [`CallSite`].
+#[derive(Debug)]
+pub struct TaskFailure {
+    call_site: Option<CallSite>,
+    kind: FailureKind,
+}
+
+#[derive(Debug)]
+enum FailureKind {
+    Panic {
+        // `Mutex` keeps `TaskFailure: Sync` without requiring a `Sync` payload.
+        payload: Mutex<Box<dyn Any + Send + 'static>>,
+        message: Option<String>,
+    },
+    // `Arc` so failures shared by coalesced calls can be cloned. `None` when a batched call
+    // was dropped without running.
+    Cancelled(Option<std::sync::Arc<tokio::task::JoinError>>),
+}
+
+impl TaskFailure {
+    /// Creates a failure from the panic payload of a blocking call.
+    #[must_use]
+    pub fn from_panic(payload: Box<dyn Any + Send + 'static>, call_site: CallSite) -> Self {
+        TaskFailure {
+            call_site: Some(call_site),
+            kind: FailureKind::panic(payload),
+        }
+    }
+
+    /// Creates a failure from the `JoinError` of a spawned blocking task.
+    #[must_use]
+    pub fn from_join_error(err: tokio::task::JoinError, call_site: CallSite) -> Self {
+        TaskFailure {
+            call_site: Some(call_site),
+            kind: FailureKind::from(err),
+        }
+    }
+
+    /// A failure for a blocking call that was dropped before it ran.
+    pub(crate) fn cancelled(call_site: CallSite) -> Self {
+        TaskFailure {
+            call_site: Some(call_site),
+            kind: FailureKind::Cancelled(None),
+        }
+    }
+
+    /// Returns `true` if the blocking call panicked.
+    pub fn is_panic(&self) -> bool {
+        matches!(self.kind, FailureKind::Panic { .. })
+    }
+
+    /// Returns `true` if the blocking task was cancelled before it completed.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.kind, FailureKind::Cancelled(_))
+    }
+
+    /// Returns the panic message, if the blocking call panicked with a string payload.
+    pub fn panic_message(&self) -> Option<&str> {
+        match &self.kind {
+            FailureKind::Panic { message, .. } => message.as_deref(),
+            FailureKind::Cancelled(_) => None,
+        }
+    }
+
+    /// Consumes the failure, returning the panic payload if the blocking call panicked.
+    ///
+    /// The payload can be passed to [`std::panic::resume_unwind`] to re-raise the panic.
+    pub fn into_panic(self) -> Option<Box<dyn Any + Send + 'static>> {
+        match self.kind {
+            FailureKind::Panic { payload, .. } => Some(
+                payload
+                    .into_inner()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner),
+            ),
+            FailureKind::Cancelled(_) => None,
+        }
+    }
+
+    /// The generated method that failed, if known.
+    ///
+    /// This is `None` only for failures converted from a bare `JoinError`.
+    pub fn call_site(&self) -> Option<CallSite> {
+        self.call_site
+    }
+}
+
+/// Clones share the call site and panic message, but the panic payload itself can't be cloned:
+/// [`into_panic`](TaskFailure::into_panic) on a clone returns the message as a `String`, or `()`
+/// if the panic had no string message.
+impl Clone for TaskFailure {
+    fn clone(&self) -> Self {
+        let kind = match &self.kind {
+            FailureKind::Panic { message, .. } => FailureKind::Panic {
+                payload: Mutex::new(match message {
+                    Some(message) => Box::new(message.clone()),
+                    None => Box::new(()),
+                }),
+                message: message.clone(),
+            },
+            FailureKind::Cancelled(err) => FailureKind::Cancelled(err.clone()),
+        };
+        TaskFailure {
+            call_site: self.call_site,
+            kind,
+        }
+    }
+}
+
+impl FailureKind {
+    fn panic(payload: Box<dyn Any + Send + 'static>) -> Self {
+        let message = panic_message(payload.as_ref()).map(str::to_owned);
+        FailureKind::Panic {
+            payload: Mutex::new(payload),
+            message,
+        }
+    }
+}
+
+/// Extracts the message of a panic payload created by `panic!` with a string.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    payload
+        .downcast_ref::<&'static str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+}
+
+impl From<tokio::task::JoinError> for FailureKind {
+    fn from(err: tokio::task::JoinError) -> Self {
+        match err.try_into_panic() {
+            Ok(payload) => FailureKind::panic(payload),
+            Err(err) => FailureKind::Cancelled(Some(std::sync::Arc::new(err))),
+        }
+    }
+}
+
+impl From<tokio::task::JoinError> for TaskFailure {
+    fn from(err: tokio::task::JoinError) -> Self {
+        TaskFailure {
+            call_site: None,
+            kind: FailureKind::from(err),
+        }
+    }
+}
+
+impl std::fmt::Display for TaskFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.call_site {
+            Some(call_site) => write!(f, "`{call_site}` ")?,
+            None => write!(f, "blocking call ")?,
+        }
+        match &self.kind {
+            FailureKind::Panic {
+                message: Some(msg), ..
+            } => write!(f, "panicked: {msg}"),
+            FailureKind::Panic { message: None, .. } => write!(f, "panicked"),
+            FailureKind::Cancelled(_) => write!(f, "was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for TaskFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            FailureKind::Cancelled(e) => e.as_deref().map(|e| e as _),
+            FailureKind::Panic { .. } => None,
+        }
+    }
+}
+
+/// Support code for the generated wrappers. Not public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::batch::Batcher;
+    pub use crate::cache::{Cache, CacheKey, CacheOutput};
+    pub use crate::coalesce::{CoalesceKey, CoalesceOutput, Flights};
+    pub use crate::facade::ensure_outside_runtime;
+    pub use crate::observe::CallObservation;
+    pub use crate::pool::Pool;
+    pub use crate::slow::SlowCallTimer;
+    pub use crate::state::Key;
+    pub use crate::subscribe::{channel as subscription_channel, EventReceiver, EventSender};
+    #[cfg(feature = "testing")]
+    pub use crate::testing::injected_failure;
+    pub use crate::thread_hooks::thread_init;
+
+    /// Without the `testing` feature, no faults can be injected.
+    #[cfg(not(feature = "testing"))]
+    #[allow(clippy::unused_async)]
+    pub async fn injected_failure(
+        _call_site: crate::CallSite,
+        _runtime: Option<&tokio::runtime::Handle>,
+    ) -> Option<crate::TaskFailure> {
+        None
+    }
+
+    /// Clones arguments between retry attempts, with a targeted error when one isn't `Clone`.
+    #[diagnostic::on_unimplemented(
+        message = "`{Self}` must implement `Clone` to be passed to a method with `retry`",
+        label = "this argument is cloned before every attempt",
+        note = "`#[async_wrap(retry(...))]` calls the blocking method again with the same arguments"
+    )]
+    pub trait RetryArg {
+        #[must_use]
+        fn retry_clone(&self) -> Self;
+    }
+
+    impl<T: Clone> RetryArg for T {
+        fn retry_clone(&self) -> Self {
+            self.clone()
         }
     }
 }
@@ -0,0 +1,242 @@
+//! Mocks of async wrappers for `#[blocking_impl(..., generate_mock = true)]`.
+//!
+//! With the `mock` feature, each such wrapper `AsyncClient` gets a `MockAsyncClient` with the
+//! same async methods. A mocked method answers like the blocking method would: an expectation
+//! gives the blocking method's return value, or a panic, and the mock turns it into the
+//! wrapper's result.
+//!
+//! ```ignore
+//! let mut client = MockAsyncClient::new();
+//! client
+//!     .expect_fetch()
+//!     .with(|&(id,)| id == 7)
+//!     .returns(Ok("seven".to_string()))
+//!     .times(1);
+//! client.expect_fetch().panics("backend crashed");
+//!
+//! assert_eq!(client.fetch(7).await?, "seven");
+//! assert!(client.fetch(8).await.is_err());
+//! assert_eq!(client.fetch_calls().len(), 2);
+//! ```
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::{CallSite, TaskFailure};
+
+type Matcher<A> = Box<dyn Fn(&A) -> bool + Send + Sync>;
+type Responder<A, R> = Box<dyn FnMut(&A) -> R + Send>;
+
+enum Response<A, R> {
+    Returning(Responder<A, R>),
+    Panic(String),
+}
+
+/// One method of a generated mock: its expectations, and the calls it received.
+///
+/// `A` is the tuple of the method's arguments and `R` the return type of the blocking method.
+pub struct MockMethod<A, R> {
+    call_site: CallSite,
+    expectations: Mutex<Vec<Expectation<A, R>>>,
+    calls: Mutex<Vec<A>>,
+}
+
+impl<A, R> MockMethod<A, R> {
+    #[doc(hidden)]
+    #[must_use]
+    pub fn new(call_site: CallSite) -> Self {
+        MockMethod {
+            call_site,
+            expectations: Mutex::default(),
+            calls: Mutex::default(),
+        }
+    }
+
+    /// Adds an expectation. Each call is answered by the first expectation that accepts its
+    /// arguments and hasn't been used up.
+    pub fn expect(&mut self) -> &mut Expectation<A, R> {
+        let expectations = self
+            .expectations
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        expectations.push(Expectation::new());
+        let last = expectations.len() - 1;
+        &mut expectations[last]
+    }
+
+    /// The arguments of every call so far, oldest first.
+    pub fn calls(&self) -> CallLog<'_, A> {
+        CallLog(self.calls.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Answers a call to the mocked method.
+    ///
+    /// # Panics
+    ///
+    /// If no expectation accepts the call, or the accepting one has no response.
+    #[doc(hidden)]
+    pub async fn call(&self, args: A) -> Result<R, TaskFailure> {
+        let (delay, response) = {
+            let mut expectations = self
+                .expectations
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let Some(expectation) = expectations
+                .iter_mut()
+                .find(|expectation| expectation.accepts(&args))
+            else {
+                drop(expectations);
+                panic!("unexpected call to {}", self.call_site);
+            };
+            expectation.calls += 1;
+            let response = match &mut expectation.response {
+                Some(Response::Returning(responder)) => Ok(responder(&args)),
+                Some(Response::Panic(message)) => Err(message.clone()),
+                None => {
+                    drop(expectations);
+                    panic!(
+                        "the expectation matching a call to {} has no response; \
+                         set one with `returns`, `returning` or `panics`",
+                        self.call_site
+                    );
+                }
+            };
+            (expectation.delay, response)
+        };
+        self.calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(args);
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        response.map_err(|message| TaskFailure::from_panic(Box::new(message), self.call_site))
+    }
+}
+
+/// Checks that every expectation set with [`times`](Expectation::times) got that many calls.
+impl<A, R> Drop for MockMethod<A, R> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+        let expectations = self
+            .expectations
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        for expectation in expectations.iter() {
+            if let Some(times) = expectation.times {
+                assert!(
+                    expectation.calls == times,
+                    "expected {times} call(s) to {}, got {}",
+                    self.call_site,
+                    expectation.calls
+                );
+            }
+        }
+    }
+}
+
+impl<A, R> fmt::Debug for MockMethod<A, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockMethod")
+            .field("call_site", &self.call_site)
+            .field("calls", &self.calls().len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// How a mocked method answers the calls it accepts.
+pub struct Expectation<A, R> {
+    matcher: Option<Matcher<A>>,
+    response: Option<Response<A, R>>,
+    delay: Duration,
+    times: Option<usize>,
+    calls: usize,
+}
+
+impl<A, R> Expectation<A, R> {
+    fn new() -> Self {
+        Expectation {
+            matcher: None,
+            response: None,
+            delay: Duration::ZERO,
+            times: None,
+            calls: 0,
+        }
+    }
+
+    /// Accepts only calls whose arguments `matcher` returns `true` for. Without one, every
+    /// call is accepted.
+    pub fn with(&mut self, matcher: impl Fn(&A) -> bool + Send + Sync + 'static) -> &mut Self {
+        self.matcher = Some(Box::new(matcher));
+        self
+    }
+
+    /// Answers with what `responder` returns for the call's arguments.
+    pub fn returning(&mut self, responder: impl FnMut(&A) -> R + Send + 'static) -> &mut Self {
+        self.response = Some(Response::Returning(Box::new(responder)));
+        self
+    }
+
+    /// Answers with a clone of `value`. For an error, pass the blocking method's `Err`.
+    pub fn returns(&mut self, value: R) -> &mut Self
+    where
+        R: Clone + Send + 'static,
+    {
+        self.returning(move |_| value.clone())
+    }
+
+    /// Answers as if the blocking method panicked with `message`.
+    pub fn panics(&mut self, message: impl Into<String>) -> &mut Self {
+        self.response = Some(Response::Panic(message.into()));
+        self
+    }
+
+    /// Waits `delay` before answering.
+    pub fn delay(&mut self, delay: Duration) -> &mut Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Answers exactly `times` calls. Later calls go to the next accepting expectation, and
+    /// dropping the mock panics if fewer calls arrived.
+    pub fn times(&mut self, times: usize) -> &mut Self {
+        self.times = Some(times);
+        self
+    }
+
+    fn accepts(&self, args: &A) -> bool {
+        self.times.is_none_or(|times| self.calls < times)
+            && self.matcher.as_ref().is_none_or(|matcher| matcher(args))
+    }
+}
+
+impl<A, R> fmt::Debug for Expectation<A, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expectation")
+            .field("delay", &self.delay)
+            .field("times", &self.times)
+            .field("calls", &self.calls)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The recorded arguments of a mocked method, locked while borrowed.
+pub struct CallLog<'a, A>(MutexGuard<'a, Vec<A>>);
+
+impl<A> Deref for CallLog<'_, A> {
+    type Target = [A];
+
+    fn deref(&self) -> &[A] {
+        &self.0
+    }
+}
+
+impl<A: fmt::Debug> fmt::Debug for CallLog<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.iter()).finish()
+    }
+}
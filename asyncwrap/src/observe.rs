@@ -0,0 +1,134 @@
+//! Observer hooks for `#[blocking_impl(..., observer = PATH)]`.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+use crate::{panic_message, CallSite, TaskFailure};
+
+/// Receives events about every blocking call made by the generated methods of a wrapper type.
+///
+/// Register an observer with `#[blocking_impl(AsyncClient, observer = METRICS)]`, where
+/// `METRICS` is a `static` implementing this trait. All methods have empty default
+/// implementations, so an observer only needs to implement the events it cares about.
+///
+/// For each call, `on_start` is followed by exactly one `on_complete`. Keeping a gauge that
+/// `on_start` increments and `on_complete` decrements gives the number of in-flight calls.
+/// Each attempt of a retried method is reported as a separate call.
+pub trait WrapObserver: Send + Sync {
+    /// The async method is about to hand the call to the blocking strategy.
+    fn on_start(&self, call_site: CallSite) {
+        let _ = call_site;
+    }
+
+    /// The blocking call started running, `queue_delay` after `on_start`.
+    ///
+    /// With `spawn_blocking` this is the time spent waiting for a blocking-pool thread.
+    fn on_spawned(&self, call_site: CallSite, queue_delay: Duration) {
+        let _ = (call_site, queue_delay);
+    }
+
+    /// The call finished. For panics this follows `on_panic`.
+    fn on_complete(&self, call_site: CallSite, outcome: CallOutcome, timing: CallTiming) {
+        let _ = (call_site, outcome, timing);
+    }
+
+    /// The blocking call panicked, with the panic message if it was a string.
+    fn on_panic(&self, call_site: CallSite, message: Option<&str>) {
+        let _ = (call_site, message);
+    }
+}
+
+/// How an observed call ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallOutcome {
+    /// The blocking method returned a value (`Ok` for methods returning `Result`).
+    Ok,
+    /// The blocking method returned `Err`.
+    Err,
+    /// The blocking method panicked.
+    Panicked,
+    /// The task was cancelled before the blocking method ran.
+    Cancelled,
+}
+
+/// Where an observed call spent its time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallTiming {
+    /// From `on_start` until the blocking call started running.
+    pub queued: Duration,
+    /// Running the blocking call itself.
+    pub running: Duration,
+}
+
+impl CallTiming {
+    /// The total time from `on_start` until the call finished.
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.queued + self.running
+    }
+}
+
+/// Tracks one observed call from the async side into the blocking closure.
+#[derive(Clone, Copy)]
+pub struct CallObservation {
+    observer: &'static dyn WrapObserver,
+    call_site: CallSite,
+    started: Instant,
+}
+
+impl CallObservation {
+    pub fn start(observer: &'static dyn WrapObserver, call_site: CallSite) -> Self {
+        observer.on_start(call_site);
+        CallObservation {
+            observer,
+            call_site,
+            started: Instant::now(),
+        }
+    }
+
+    /// Runs the blocking call, reporting when it starts and how it ends.
+    ///
+    /// Panics are reported and then resumed, so the strategy handles them as usual.
+    pub fn run<R>(self, call: impl FnOnce() -> R, is_ok: impl FnOnce(&R) -> bool) -> R {
+        let spawned = Instant::now();
+        let queued = spawned - self.started;
+        self.observer.on_spawned(self.call_site, queued);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(call));
+        let timing = CallTiming {
+            queued,
+            running: spawned.elapsed(),
+        };
+
+        match result {
+            Ok(value) => {
+                let outcome = if is_ok(&value) {
+                    CallOutcome::Ok
+                } else {
+                    CallOutcome::Err
+                };
+                self.observer.on_complete(self.call_site, outcome, timing);
+                value
+            }
+            Err(payload) => {
+                self.observer
+                    .on_panic(self.call_site, panic_message(payload.as_ref()));
+                self.observer
+                    .on_complete(self.call_site, CallOutcome::Panicked, timing);
+                panic::resume_unwind(payload)
+            }
+        }
+    }
+
+    /// Reports a task that was cancelled before [`run`](Self::run) was reached.
+    pub fn report_failure(&self, failure: &TaskFailure) {
+        if failure.is_cancelled() {
+            let timing = CallTiming {
+                queued: self.started.elapsed(),
+                running: Duration::ZERO,
+            };
+            self.observer
+                .on_complete(self.call_site, CallOutcome::Cancelled, timing);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Dedicated blocking thread pools for `#[blocking_impl(..., pool = "name")]`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+use tokio::runtime::{Builder, Handle, Runtime};
+
+type Hook = Arc<dyn Fn() + Send + Sync>;
+
+fn pools() -> &'static Mutex<HashMap<String, Runtime>> {
+    static POOLS: OnceLock<Mutex<HashMap<String, Runtime>>> = OnceLock::new();
+    POOLS.get_or_init(Mutex::default)
+}
+
+/// Configures a named blocking thread pool.
+///
+/// Wrappers declared with `pool = "name"` run their blocking calls on the pool registered
+/// under that name instead of Tokio's shared blocking pool. A pool used before it is
+/// registered is started with the default settings.
+///
+/// ```ignore
+/// asyncwrap::PoolBuilder::new("devices")
+///     .size(8)
+///     .thread_name_prefix("device-io")
+///     .register()?;
+/// ```
+#[must_use = "the pool is only created by `register`"]
+pub struct PoolBuilder {
+    name: String,
+    size: usize,
+    thread_name_prefix: Option<String>,
+    stack_size: Option<usize>,
+    on_thread_start: Option<Hook>,
+    on_thread_stop: Option<Hook>,
+}
+
+impl PoolBuilder {
+    /// Settings for the pool `name`: up to 64 threads named `<name>-<n>`, with Tokio's
+    /// default stack size.
+    pub fn new(name: impl Into<String>) -> Self {
+        PoolBuilder {
+            name: name.into(),
+            size: 64,
+            thread_name_prefix: None,
+            stack_size: None,
+            on_thread_start: None,
+            on_thread_stop: None,
+        }
+    }
+
+    /// The maximum number of threads. Calls beyond it wait for a free thread. `register`
+    /// rejects a size of 0.
+    pub fn size(mut self, size: usize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Names threads `<prefix>-<n>` instead of `<name>-<n>`.
+    pub fn thread_name_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.thread_name_prefix = Some(prefix.into());
+        self
+    }
+
+    /// The stack size of each thread, in bytes.
+    pub fn stack_size(mut self, bytes: usize) -> Self {
+        self.stack_size = Some(bytes);
+        self
+    }
+
+    /// Runs `hook` on each thread when it starts.
+    pub fn on_thread_start(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_thread_start = Some(Arc::new(hook));
+        self
+    }
+
+    /// Runs `hook` on each thread before it exits.
+    pub fn on_thread_stop(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_thread_stop = Some(Arc::new(hook));
+        self
+    }
+
+    /// Starts the pool.
+    ///
+    /// # Errors
+    ///
+    /// If the size is 0, a pool with this name already exists, or its threads can't be set up.
+    pub fn register(self) -> Result<(), PoolError> {
+        if self.size == 0 {
+            return Err(PoolError::ZeroSize(self.name));
+        }
+        let mut pools = pools().lock().unwrap_or_else(PoisonError::into_inner);
+        if pools.contains_key(&self.name) {
+            return Err(PoolError::AlreadyRegistered(self.name));
+        }
+        let runtime = self.build().map_err(PoolError::Io)?;
+        pools.insert(self.name, runtime);
+        Ok(())
+    }
+
+    fn build(&self) -> io::Result<Runtime> {
+        let prefix = self
+            .thread_name_prefix
+            .clone()
+            .unwrap_or_else(|| self.name.clone());
+        let next_thread = AtomicUsize::new(0);
+
+        // The runtime is never driven: only its blocking pool is used.
+        let mut builder = Builder::new_current_thread();
+        builder
+            .max_blocking_threads(self.size)
+            .thread_name_fn(move || {
+                format!("{prefix}-{}", next_thread.fetch_add(1, Ordering::Relaxed))
+            });
+        if let Some(stack_size) = self.stack_size {
+            builder.thread_stack_size(stack_size);
+        }
+        if let Some(hook) = self.on_thread_start.clone() {
+            builder.on_thread_start(move || hook());
+        }
+        if let Some(hook) = self.on_thread_stop.clone() {
+            builder.on_thread_stop(move || hook());
+        }
+        builder.build()
+    }
+}
+
+impl fmt::Debug for PoolBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolBuilder")
+            .field("name", &self.name)
+            .field("size", &self.size)
+            .field("thread_name_prefix", &self.thread_name_prefix)
+            .field("stack_size", &self.stack_size)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Why a pool couldn't be registered.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PoolError {
+    /// A pool with this name was already registered, or already started by a call.
+    AlreadyRegistered(String),
+    /// The pool was given a size of 0, so no call could ever run on it.
+    ZeroSize(String),
+    /// The pool's threads couldn't be set up.
+    Io(io::Error),
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::AlreadyRegistered(name) => {
+                write!(f, "blocking pool \"{name}\" is already registered")
+            }
+            PoolError::ZeroSize(name) => {
+                write!(f, "blocking pool \"{name}\" must have at least one thread")
+            }
+            PoolError::Io(err) => write!(f, "failed to start blocking pool: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PoolError::AlreadyRegistered(_) | PoolError::ZeroSize(_) => None,
+            PoolError::Io(err) => Some(err),
+        }
+    }
+}
+
+/// A reference to a named pool from generated code, which looks the pool up on first use.
+pub struct Pool {
+    name: &'static str,
+    handle: OnceLock<Handle>,
+}
+
+impl Pool {
+    #[must_use]
+    pub const fn new(name: &'static str) -> Self {
+        Pool {
+            name,
+            handle: OnceLock::new(),
+        }
+    }
+
+    /// The pool's handle, starting it with the default settings if it isn't registered.
+    ///
+    /// # Panics
+    ///
+    /// If the pool has to be started and its threads can't be set up.
+    pub fn handle(&self) -> &Handle {
+        self.handle.get_or_init(|| {
+            let mut pools = pools().lock().unwrap_or_else(PoisonError::into_inner);
+            let runtime = pools.entry(self.name.to_owned()).or_insert_with(|| {
+                PoolBuilder::new(self.name).build().unwrap_or_else(|err| {
+                    panic!("failed to start blocking pool \"{}\": {err}", self.name)
+                })
+            });
+            runtime.handle().clone()
+        })
+    }
+}
@@ -0,0 +1,125 @@
+//! Progress reporting from blocking methods taking an [`asyncwrap::Progress`](Progress).
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tokio::sync::watch;
+
+/// The latest progress reported by a blocking call.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ProgressState {
+    /// Units of work done so far.
+    pub completed: u64,
+    /// Units of work in total, if known.
+    pub total: Option<u64>,
+    /// A description of the current step.
+    pub message: Option<String>,
+}
+
+impl ProgressState {
+    /// The fraction of the work done, between 0 and 1, if the total is known and non-zero.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn fraction(&self) -> Option<f64> {
+        let total = self.total.filter(|&total| total > 0)?;
+        Some((self.completed as f64 / total as f64).min(1.0))
+    }
+}
+
+/// A handle a blocking method reports its progress through.
+///
+/// Async wrappers of methods with an argument written `asyncwrap::Progress` create the handle
+/// themselves and return a [`WithProgress`] future. Blocking callers can create one with
+/// [`Progress::channel`], or pass `Progress::default()` to discard updates.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    sender: Arc<watch::Sender<ProgressState>>,
+}
+
+impl Progress {
+    /// Creates a handle and a receiver watching its updates.
+    #[must_use]
+    pub fn channel() -> (Progress, watch::Receiver<ProgressState>) {
+        let (sender, receiver) = watch::channel(ProgressState::default());
+        (
+            Progress {
+                sender: Arc::new(sender),
+            },
+            receiver,
+        )
+    }
+
+    /// Sets the total units of work.
+    pub fn set_total(&self, total: u64) {
+        self.update(|state| state.total = Some(total));
+    }
+
+    /// Sets the units of work done so far.
+    pub fn set_completed(&self, completed: u64) {
+        self.update(|state| state.completed = completed);
+    }
+
+    /// Adds `amount` to the units of work done.
+    pub fn advance(&self, amount: u64) {
+        self.update(|state| state.completed = state.completed.saturating_add(amount));
+    }
+
+    /// Sets the description of the current step.
+    pub fn set_message(&self, message: impl Into<String>) {
+        let message = message.into();
+        self.update(|state| state.message = Some(message));
+    }
+
+    /// The latest reported state.
+    #[must_use]
+    pub fn current(&self) -> ProgressState {
+        self.sender.borrow().clone()
+    }
+
+    /// Modifies the state in place, notifying receivers once.
+    pub fn update(&self, modify: impl FnOnce(&mut ProgressState)) {
+        self.sender.send_modify(modify);
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress::channel().0
+    }
+}
+
+/// The future returned by the async wrapper of a method taking a [`Progress`].
+///
+/// It resolves to the wrapper's result; [`progress`](WithProgress::progress) watches the
+/// updates made in the meantime.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WithProgress<F> {
+    progress: watch::Receiver<ProgressState>,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> WithProgress<F> {
+    #[doc(hidden)]
+    pub fn new(progress: watch::Receiver<ProgressState>, future: F) -> Self {
+        WithProgress {
+            progress,
+            future: Box::pin(future),
+        }
+    }
+
+    /// A receiver watching the call's progress, starting from the latest update.
+    #[must_use]
+    pub fn progress(&self) -> watch::Receiver<ProgressState> {
+        self.progress.clone()
+    }
+}
+
+impl<F: Future> Future for WithProgress<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        self.future.as_mut().poll(cx)
+    }
+}
@@ -0,0 +1,79 @@
+//! Retry policies for `#[async_wrap(retry(...))]`.
+
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+use tokio::time::Sleep;
+
+/// How the delay between attempts grows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// Waits `base` between every attempt.
+    Fixed,
+    /// Waits `base * n` after the `n`-th failed attempt.
+    Linear,
+    /// Waits `base * 2^(n - 1)` after the `n`-th failed attempt.
+    Exponential,
+}
+
+/// The retry policy of a generated method.
+///
+/// Generated code builds one of these as a `const` from the `retry(...)` options, re-runs the
+/// blocking call while [`should_retry`](Self::should_retry) allows it, and awaits
+/// [`backoff`](Self::backoff) in between, so no blocking thread is held while waiting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Backoff,
+    base: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a policy making at most `max_attempts` calls, including the first one.
+    #[must_use]
+    pub const fn new(max_attempts: u32, backoff: Backoff, base: Duration) -> Self {
+        RetryPolicy {
+            max_attempts,
+            backoff,
+            base,
+        }
+    }
+
+    /// The maximum number of calls, including the first one.
+    #[must_use]
+    pub const fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns `true` if another attempt may follow the failed attempt number `attempt`.
+    #[must_use]
+    pub const fn should_retry(&self, attempt: u32) -> bool {
+        attempt < self.max_attempts
+    }
+
+    /// The delay to wait after the failed attempt number `attempt` (starting at 1).
+    #[must_use]
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let attempt = attempt.max(1);
+        match self.backoff {
+            Backoff::Fixed => self.base,
+            Backoff::Linear => self.base.saturating_mul(attempt),
+            Backoff::Exponential => {
+                let factor = 1u32.checked_shl(attempt - 1).unwrap_or(u32::MAX);
+                self.base.saturating_mul(factor)
+            }
+        }
+    }
+
+    /// Sleeps for [`delay(attempt)`](Self::delay) without blocking the executor.
+    pub async fn backoff(&self, attempt: u32) {
+        tokio::time::sleep(self.delay(attempt)).await;
+    }
+
+    /// Like [`backoff`](Self::backoff), with a timer of `runtime`, so it can be awaited
+    /// outside of that runtime.
+    pub fn backoff_on(&self, runtime: &Handle, attempt: u32) -> Sleep {
+        let _guard = runtime.enter();
+        tokio::time::sleep(self.delay(attempt))
+    }
+}
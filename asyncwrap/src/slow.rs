@@ -0,0 +1,103 @@
+//! Slow-call detection for `warn_after = "..."`.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::CallSite;
+
+/// A blocking call that ran longer than its `warn_after` threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SlowCall {
+    call_site: CallSite,
+    elapsed: Duration,
+    threshold: Duration,
+}
+
+impl SlowCall {
+    /// The wrapper method that made the call.
+    #[must_use]
+    pub fn call_site(&self) -> CallSite {
+        self.call_site
+    }
+
+    /// How long the blocking call ran.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The configured `warn_after` threshold.
+    #[must_use]
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+}
+
+impl fmt::Display for SlowCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` blocked for {:?}, longer than `warn_after` ({:?})",
+            self.call_site, self.elapsed, self.threshold
+        )
+    }
+}
+
+/// The default `on_slow` handler.
+///
+/// Emits a warning through `tracing` with the `tracing` feature, through `log` with the `log`
+/// feature, and to standard error otherwise.
+pub fn log_slow_call(call: &SlowCall) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        target: "asyncwrap",
+        call_site = %call.call_site,
+        elapsed = ?call.elapsed,
+        threshold = ?call.threshold,
+        "{call}"
+    );
+
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    log::warn!(target: "asyncwrap", "{call}");
+
+    #[cfg(not(any(feature = "log", feature = "tracing")))]
+    eprintln!("warning: {call}");
+}
+
+/// Times one blocking call against its `warn_after` threshold.
+pub struct SlowCallTimer {
+    call_site: CallSite,
+    threshold: Duration,
+    started: Instant,
+}
+
+impl SlowCallTimer {
+    #[must_use]
+    pub fn start(call_site: CallSite, threshold: Duration) -> Self {
+        SlowCallTimer {
+            call_site,
+            threshold,
+            started: Instant::now(),
+        }
+    }
+
+    /// Reports the call to `on_slow` if it exceeded the threshold, then panics if `panic` is set.
+    ///
+    /// # Panics
+    ///
+    /// If the call was slow and `panic` is set.
+    pub fn finish(self, on_slow: fn(&SlowCall), panic: bool) {
+        let elapsed = self.started.elapsed();
+        if elapsed <= self.threshold {
+            return;
+        }
+
+        let call = SlowCall {
+            call_site: self.call_site,
+            elapsed,
+            threshold: self.threshold,
+        };
+        on_slow(&call);
+        assert!(!panic, "{call}");
+    }
+}
@@ -0,0 +1,82 @@
+//! Per-instance storage for methods that keep state between calls.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex, PoisonError};
+
+type Slot = Arc<dyn Any + Send + Sync>;
+
+/// State shared between calls of the generated methods of one async wrapper.
+///
+/// Methods using `coalesce` keep their in-flight calls here, methods using `cache` their
+/// results and methods using `batch` their pending batches. Add a `WrapState` field named
+/// `state` to the async type, or name another field with
+/// `#[blocking_impl(..., state = "...")]`.
+#[derive(Default)]
+pub struct WrapState {
+    slots: Mutex<HashMap<(&'static str, TypeId), Slot>>,
+}
+
+impl WrapState {
+    /// Creates empty state.
+    #[must_use]
+    pub fn new() -> Self {
+        WrapState::default()
+    }
+
+    /// The `T` kept for `method`, created on first use.
+    #[doc(hidden)]
+    pub fn slot<T: Default + Send + Sync + 'static>(&self, method: &'static str) -> Arc<T> {
+        let slot = Arc::clone(
+            self.slots
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry((method, TypeId::of::<T>()))
+                .or_insert_with(|| Arc::new(T::default())),
+        );
+        slot.downcast()
+            .unwrap_or_else(|_| unreachable!("slots are keyed by their type"))
+    }
+}
+
+impl fmt::Debug for WrapState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WrapState").finish_non_exhaustive()
+    }
+}
+
+/// A type-erased argument of a call, used to find calls with equal arguments.
+#[derive(Clone)]
+pub struct Key {
+    hash: u64,
+    value: Arc<dyn Any + Send + Sync>,
+    eq: fn(&dyn Any, &dyn Any) -> bool,
+}
+
+impl Key {
+    pub(crate) fn new<T: Hash + Eq + Clone + Send + Sync + 'static>(value: &T) -> Self {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        Key {
+            hash: hasher.finish(),
+            value: Arc::new(value.clone()),
+            eq: |a, b| a.downcast_ref::<T>() == b.downcast_ref::<T>(),
+        }
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Key) -> bool {
+        self.hash == other.hash && (self.eq)(&*self.value, &*other.value)
+    }
+}
+
+impl Eq for Key {}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
@@ -0,0 +1,248 @@
+//! Callback-to-stream bridging for `#[async_wrap(subscribe)]`.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+use tokio::runtime::Handle;
+
+/// What a subscription does with an event that arrives while its buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Overflow {
+    /// Discard the oldest buffered event to make room (`overflow = "drop_oldest"`).
+    DropOldest,
+    /// Discard the new event (`overflow = "drop_newest"`).
+    DropNewest,
+    /// Block the thread running the callback until there is room (`overflow = "block"`).
+    Block,
+}
+
+struct Shared<T> {
+    capacity: usize,
+    overflow: Overflow,
+    state: Mutex<State<T>>,
+    space: Condvar,
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    waker: Option<Waker>,
+    senders: usize,
+    closed: bool,
+    dropped: u64,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Creates the channel between a subscription callback and its [`Subscription`].
+#[must_use]
+pub fn channel<T>(capacity: usize, overflow: Overflow) -> (EventSender<T>, EventReceiver<T>) {
+    let shared = Arc::new(Shared {
+        capacity,
+        overflow,
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            waker: None,
+            senders: 1,
+            closed: false,
+            dropped: 0,
+        }),
+        space: Condvar::new(),
+    });
+    (
+        EventSender {
+            shared: Arc::clone(&shared),
+        },
+        EventReceiver { shared },
+    )
+}
+
+/// The sending half, captured by the callback passed to the blocking method.
+pub struct EventSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> EventSender<T> {
+    /// Buffers `event` according to the overflow policy. Events sent after the subscription
+    /// is dropped are discarded.
+    pub fn send(&self, event: T) {
+        let shared = &*self.shared;
+        let mut state = shared.lock();
+        while !state.closed && state.queue.len() >= shared.capacity {
+            match shared.overflow {
+                Overflow::DropOldest => {
+                    state.queue.pop_front();
+                    state.dropped += 1;
+                }
+                Overflow::DropNewest => {
+                    state.dropped += 1;
+                    return;
+                }
+                Overflow::Block => {
+                    state = shared
+                        .space
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+        if state.closed {
+            return;
+        }
+
+        state.queue.push_back(event);
+        let waker = state.waker.take();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Clone for EventSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        EventSender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for EventSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            if let Some(waker) = state.waker.take() {
+                drop(state);
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// The receiving half, turned into a [`Subscription`] once the blocking method succeeds.
+pub struct EventReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> EventReceiver<T> {
+    fn close(&self) {
+        let mut state = self.shared.lock();
+        state.closed = true;
+        state.queue.clear();
+        drop(state);
+        self.shared.space.notify_all();
+    }
+}
+
+impl<T> Drop for EventReceiver<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// A stream of the events passed to a callback registered by `#[async_wrap(subscribe)]`.
+///
+/// Dropping the subscription discards buffered events and calls the `unsubscribe` method, if
+/// one was configured. The stream ends once the blocking code drops its callback.
+pub struct Subscription<'a, T> {
+    events: EventReceiver<T>,
+    unsubscribe: Option<Box<dyn FnOnce() + Send + 'a>>,
+}
+
+impl<'a, T> Subscription<'a, T> {
+    /// A subscription that runs `unsubscribe` on drop.
+    #[doc(hidden)]
+    #[must_use]
+    pub fn new(events: EventReceiver<T>, unsubscribe: impl FnOnce() + Send + 'a) -> Self {
+        Subscription {
+            events,
+            unsubscribe: Some(Box::new(unsubscribe)),
+        }
+    }
+
+    /// Receives the next event, or `None` once the callback has been dropped.
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// The number of events discarded so far because the buffer was full.
+    #[must_use]
+    pub fn dropped(&self) -> u64 {
+        self.events.shared.lock().dropped
+    }
+
+    fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut state = self.events.shared.lock();
+        if let Some(event) = state.queue.pop_front() {
+            drop(state);
+            self.events.shared.space.notify_one();
+            return Poll::Ready(Some(event));
+        }
+        if state.senders == 0 {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Subscription<'static, T> {
+    /// A subscription without an unsubscribe method.
+    #[doc(hidden)]
+    #[must_use]
+    pub fn without_unsubscribe(events: EventReceiver<T>) -> Self {
+        Subscription {
+            events,
+            unsubscribe: None,
+        }
+    }
+
+    /// A subscription that runs the blocking `unsubscribe` on the blocking pool when dropped
+    /// inside a runtime, and inline otherwise.
+    #[doc(hidden)]
+    #[must_use]
+    pub fn detached(events: EventReceiver<T>, unsubscribe: impl FnOnce() + Send + 'static) -> Self {
+        Subscription::new(events, move || match Handle::try_current() {
+            Ok(handle) => drop(handle.spawn_blocking(unsubscribe)),
+            Err(_) => unsubscribe(),
+        })
+    }
+}
+
+impl<T> Stream for Subscription<'_, T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.poll_recv(cx)
+    }
+}
+
+impl<T> Drop for Subscription<'_, T> {
+    fn drop(&mut self) {
+        // Close first, so a callback blocked on a full buffer can't hold up the unsubscribe.
+        self.events.close();
+        if let Some(unsubscribe) = self.unsubscribe.take() {
+            unsubscribe();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Subscription<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.events.shared.lock();
+        f.debug_struct("Subscription")
+            .field("buffered", &state.queue.len())
+            .field("dropped", &state.dropped)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,176 @@
+//! Fault injection for testing how callers handle task failures.
+//!
+//! The methods of a wrapper with `fault_injection = true` that report failures through
+//! `AsyncWrapError::TaskFailed` check the injected faults before each blocking call. A
+//! matching fault makes the call fail as if the blocking task had panicked or been cancelled,
+//! or delays it, without running the blocking method for failures. Other wrappers, and plain
+//! `block_in_place` methods, which don't report failures, aren't affected. Without the
+//! `testing` feature, the check does nothing.
+//!
+//! ```ignore
+//! use asyncwrap::testing::{self, Fault};
+//!
+//! #[blocking_impl(AsyncClient, fault_injection = true)]
+//! impl BlockingClient { /* ... */ }
+//!
+//! let _fault = testing::inject_into("AsyncClient", Fault::panic("backend crashed").times(1));
+//! assert!(client.fetch(1).await.is_err());
+//! assert!(client.fetch(1).await.is_ok());
+//! ```
+//!
+//! Faults stay in place until their [`FaultGuard`] is dropped. They are global, so tests
+//! running in parallel see each other's faults: prefer targeting a wrapper or method that only
+//! one test uses.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+
+use crate::{CallSite, TaskFailure};
+
+static FAULTS: Mutex<Vec<Injected>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// What happens to a call a fault applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fault {
+    kind: FaultKind,
+    times: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FaultKind {
+    Panic(String),
+    Cancel,
+    Delay(Duration),
+}
+
+impl Fault {
+    /// Fails the call as if the blocking method panicked with `message`.
+    #[must_use]
+    pub fn panic(message: impl Into<String>) -> Self {
+        Fault::new(FaultKind::Panic(message.into()))
+    }
+
+    /// Fails the call as if its blocking task was cancelled.
+    #[must_use]
+    pub fn cancel() -> Self {
+        Fault::new(FaultKind::Cancel)
+    }
+
+    /// Waits `delay` before making the blocking call, on the wrapper's `handle` runtime if it
+    /// has one.
+    #[must_use]
+    pub fn delay(delay: Duration) -> Self {
+        Fault::new(FaultKind::Delay(delay))
+    }
+
+    /// Applies to the next `times` calls only, instead of every call.
+    #[must_use]
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    fn new(kind: FaultKind) -> Self {
+        Fault { kind, times: None }
+    }
+}
+
+struct Injected {
+    id: u64,
+    type_name: Option<String>,
+    method: Option<String>,
+    fault: Fault,
+}
+
+impl Injected {
+    fn applies_to(&self, call_site: CallSite) -> bool {
+        self.fault.times != Some(0)
+            && self
+                .type_name
+                .as_ref()
+                .is_none_or(|type_name| type_name == call_site.type_name())
+            && self
+                .method
+                .as_ref()
+                .is_none_or(|method| method == call_site.method())
+    }
+}
+
+/// Removes its fault when dropped.
+#[derive(Debug)]
+#[must_use = "the fault is removed when the guard is dropped"]
+pub struct FaultGuard {
+    id: u64,
+}
+
+impl Drop for FaultGuard {
+    fn drop(&mut self) {
+        faults().retain(|injected| injected.id != self.id);
+    }
+}
+
+/// Injects `fault` into the calls of every generated method.
+pub fn inject(fault: Fault) -> FaultGuard {
+    register(None, None, fault)
+}
+
+/// Injects `fault` into the calls of the wrapper named `type_name`, e.g. `"AsyncClient"`.
+pub fn inject_into(type_name: &str, fault: Fault) -> FaultGuard {
+    register(Some(type_name), None, fault)
+}
+
+/// Injects `fault` into the calls of one method of the wrapper named `type_name`.
+pub fn inject_into_method(type_name: &str, method: &str, fault: Fault) -> FaultGuard {
+    register(Some(type_name), Some(method), fault)
+}
+
+fn register(type_name: Option<&str>, method: Option<&str>, fault: Fault) -> FaultGuard {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    faults().push(Injected {
+        id,
+        type_name: type_name.map(str::to_owned),
+        method: method.map(str::to_owned),
+        fault,
+    });
+    FaultGuard { id }
+}
+
+fn faults() -> MutexGuard<'static, Vec<Injected>> {
+    FAULTS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Applies the first injected fault matching `call_site`, returning the failure the call
+/// should report instead of running. Delays use the timer of `runtime`, the wrapper's `handle`
+/// runtime, since the caller may not be in one, and otherwise the caller's.
+#[doc(hidden)]
+pub async fn injected_failure(
+    call_site: CallSite,
+    runtime: Option<&Handle>,
+) -> Option<TaskFailure> {
+    let kind = {
+        let mut faults = faults();
+        let injected = faults
+            .iter_mut()
+            .find(|injected| injected.applies_to(call_site))?;
+        if let Some(times) = &mut injected.fault.times {
+            *times -= 1;
+        }
+        injected.fault.kind.clone()
+    };
+    match kind {
+        FaultKind::Panic(message) => Some(TaskFailure::from_panic(Box::new(message), call_site)),
+        FaultKind::Cancel => Some(TaskFailure::cancelled(call_site)),
+        FaultKind::Delay(delay) => {
+            let sleep = {
+                let _runtime = runtime.map(Handle::enter);
+                tokio::time::sleep(delay)
+            };
+            sleep.await;
+            None
+        }
+    }
+}
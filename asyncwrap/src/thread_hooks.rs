@@ -0,0 +1,47 @@
+//! Per-thread setup for `#[blocking_impl(..., thread_init = path, thread_deinit = path)]`.
+
+use std::any::TypeId;
+use std::cell::RefCell;
+
+thread_local! {
+    static INITIALIZED: RefCell<Initialized> = const { RefCell::new(Initialized(Vec::new())) };
+}
+
+/// The `thread_init` hooks that ran on this thread, with their `thread_deinit` hooks.
+struct Initialized(Vec<Hook>);
+
+struct Hook {
+    init: TypeId,
+    deinit: Option<fn()>,
+}
+
+impl Drop for Initialized {
+    // Runs when the thread exits, undoing the hooks in reverse order.
+    fn drop(&mut self) {
+        for hook in self.0.drain(..).rev() {
+            if let Some(deinit) = hook.deinit {
+                deinit();
+            }
+        }
+    }
+}
+
+/// Runs `init` unless it already ran on this thread, and schedules `deinit` for when the
+/// thread exits.
+///
+/// Hooks are told apart by the type of `init`, which is unique to each function, so wrappers
+/// sharing a `thread_init` function run it once per thread between them.
+pub fn thread_init<I: Fn() + 'static>(init: I, deinit: Option<fn()>) {
+    let id = TypeId::of::<I>();
+    let initialized = INITIALIZED
+        .try_with(|hooks| hooks.borrow().0.iter().any(|hook| hook.init == id))
+        // The thread is exiting: run the hook without tracking it.
+        .unwrap_or(false);
+    if initialized {
+        return;
+    }
+
+    // Not borrowed while `init` runs, in case it calls into other wrappers.
+    init();
+    let _ = INITIALIZED.try_with(|hooks| hooks.borrow_mut().0.push(Hook { init: id, deinit }));
+}
@@ -98,6 +98,130 @@ mod block_in_place_strategy {
     }
 }
 
+mod catch_unwind {
+    use asyncwrap::blocking_impl;
+    use std::cell::Cell;
+    use thiserror::Error;
+
+    #[derive(Error, Debug, PartialEq)]
+    #[error("inner error")]
+    struct InnerError;
+
+    struct PanickingService;
+
+    #[blocking_impl(
+        AsyncPanickingService,
+        strategy = "block_in_place",
+        catch_unwind = true
+    )]
+    impl PanickingService {
+        #[async_wrap]
+        pub fn will_panic(&self) -> i32 {
+            panic!("intentional panic for testing");
+        }
+
+        #[async_wrap]
+        #[allow(clippy::unnecessary_wraps, clippy::manual_assert)]
+        pub fn might_panic(&self, should_panic: bool) -> Result<i32, InnerError> {
+            if should_panic {
+                panic!("intentional panic for testing");
+            }
+            Ok(42)
+        }
+
+        #[async_wrap]
+        pub fn always_fails(&self) -> Result<i32, InnerError> {
+            Err(InnerError)
+        }
+
+        #[async_wrap(catch_unwind = false)]
+        pub fn uncaught(&self) -> i32 {
+            7
+        }
+
+        #[async_wrap(assert_unwind_safe = false)]
+        pub fn add(&self, a: u32, b: u32) -> u32 {
+            a + b
+        }
+    }
+
+    pub struct AsyncPanickingService {
+        inner: PanickingService,
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_panic_returns_task_failed() {
+        let svc = AsyncPanickingService {
+            inner: PanickingService,
+        };
+
+        // The same shape as a non-Result method under `spawn_blocking`.
+        let result: Result<i32, asyncwrap::AsyncWrapError<std::convert::Infallible>> =
+            svc.will_panic().await;
+        let err = result.unwrap_err();
+        assert!(err.is_panic());
+        assert_eq!(
+            err.to_string(),
+            "async task failed: `AsyncPanickingService::will_panic` panicked: \
+             intentional panic for testing"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_result_shape_matches_spawn_blocking() {
+        let svc = AsyncPanickingService {
+            inner: PanickingService,
+        };
+
+        let ok: asyncwrap::AsyncWrapResult<Result<i32, InnerError>> = svc.might_panic(false).await;
+        assert_eq!(ok.unwrap(), 42);
+        assert!(matches!(
+            svc.might_panic(true).await,
+            Err(asyncwrap::AsyncWrapError::TaskFailed(_))
+        ));
+        assert!(matches!(
+            svc.always_fails().await,
+            Err(asyncwrap::AsyncWrapError::Inner(InnerError))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_per_method_opt_out() {
+        let svc = AsyncPanickingService {
+            inner: PanickingService,
+        };
+        let value: i32 = svc.uncaught().await;
+        assert_eq!(value, 7);
+        assert_eq!(svc.add(1, 2).await.unwrap(), 3);
+    }
+
+    struct CellService {
+        hits: Cell<u32>,
+    }
+
+    #[blocking_impl(AsyncCellService, strategy = "block_in_place")]
+    impl CellService {
+        #[async_wrap(catch_unwind = true)]
+        pub fn hit(&self) -> u32 {
+            self.hits.set(self.hits.get() + 1);
+            self.hits.get()
+        }
+    }
+
+    pub struct AsyncCellService {
+        inner: CellService,
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_per_method_catch_unwind() {
+        let svc = AsyncCellService {
+            inner: CellService { hits: Cell::new(0) },
+        };
+        assert_eq!(svc.hit().await.unwrap(), 1);
+        assert_eq!(svc.hit().await.unwrap(), 2);
+    }
+}
+
 mod custom_field_name {
     use asyncwrap::blocking_impl;
     use std::sync::Arc;
@@ -289,6 +413,41 @@ mod only_marked_methods {
     }
 }
 
+mod split_impls {
+    use super::*;
+
+    struct Service;
+
+    #[blocking_impl(AsyncService)]
+    impl Service {
+        #[async_wrap]
+        pub fn read(&self) -> i32 {
+            1
+        }
+    }
+
+    #[blocking_impl(AsyncService)]
+    impl Service {
+        #[async_wrap]
+        pub fn write(&self, value: i32) -> i32 {
+            value
+        }
+    }
+
+    pub struct AsyncService {
+        inner: Arc<Service>,
+    }
+
+    #[tokio::test]
+    async fn test_methods_split_across_impl_blocks() {
+        let svc = AsyncService {
+            inner: Arc::new(Service),
+        };
+        assert_eq!(svc.read().await.unwrap(), 1);
+        assert_eq!(svc.write(2).await.unwrap(), 2);
+    }
+}
+
 mod generics {
     use super::*;
 
@@ -371,97 +530,3104 @@ mod async_wrap_error {
         let err: asyncwrap::AsyncWrapError<TestError> = asyncwrap::AsyncWrapError::Inner(TestError);
         assert!(err.source().is_some());
     }
+
+    #[test]
+    fn test_inner_is_not_a_task_failure() {
+        let err: asyncwrap::AsyncWrapError<TestError> = asyncwrap::AsyncWrapError::Inner(TestError);
+        assert!(!err.is_panic());
+        assert!(!err.is_cancelled());
+        assert!(err.panic_message().is_none());
+        assert!(err.call_site().is_none());
+        assert!(err.into_panic().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_task() {
+        let handle = tokio::spawn(std::future::pending::<()>());
+        handle.abort();
+        let join_err = handle.await.unwrap_err();
+
+        let call_site = asyncwrap::CallSite::new("AsyncClient", "fetch");
+        let err: asyncwrap::AsyncWrapError<TestError> =
+            asyncwrap::TaskFailure::from_join_error(join_err, call_site).into();
+        assert!(err.is_cancelled());
+        assert!(!err.is_panic());
+        assert_eq!(err.call_site(), Some(call_site));
+        assert_eq!(
+            err.to_string(),
+            "async task failed: `AsyncClient::fetch` was cancelled"
+        );
+    }
 }
 
-mod visibility {
+mod error_combinators {
+    use asyncwrap::{AsyncWrapError, CallSite, TaskFailure};
+    use thiserror::Error;
+
+    #[derive(Error, Debug, PartialEq)]
+    #[error("test error {0}")]
+    struct TestError(u32);
+
+    fn panicked<E>() -> AsyncWrapError<E> {
+        let payload = Box::new("boom");
+        TaskFailure::from_panic(payload, CallSite::new("AsyncClient", "fetch")).into()
+    }
+
+    #[test]
+    fn test_inner_accessors() {
+        let mut err = AsyncWrapError::Inner(TestError(1));
+        assert_eq!(err.inner(), Some(&TestError(1)));
+        err.inner_mut().unwrap().0 = 2;
+        assert_eq!(err.into_inner(), Some(TestError(2)));
+
+        let mut failed = panicked::<TestError>();
+        assert!(failed.inner().is_none());
+        assert!(failed.inner_mut().is_none());
+        assert!(failed.into_inner().is_none());
+    }
+
+    #[test]
+    fn test_map_inner() {
+        let err = AsyncWrapError::Inner(TestError(3)).map_inner(|e| e.0 * 2);
+        assert_eq!(err.into_inner(), Some(6));
+
+        let failed = panicked::<TestError>().map_inner(|e| e.0);
+        assert_eq!(failed.panic_message(), Some("boom"));
+    }
+
+    #[test]
+    fn test_flatten() {
+        let nested = AsyncWrapError::Inner(AsyncWrapError::Inner(TestError(4)));
+        assert_eq!(nested.flatten().into_inner(), Some(TestError(4)));
+
+        let nested: AsyncWrapError<AsyncWrapError<TestError>> = AsyncWrapError::Inner(panicked());
+        assert!(nested.flatten().is_panic());
+
+        let outer: AsyncWrapError<AsyncWrapError<TestError>> = panicked();
+        assert!(outer.flatten().is_panic());
+    }
+
+    #[test]
+    fn test_into_io_error() {
+        let err: std::io::Error =
+            AsyncWrapError::Inner(std::io::Error::from(std::io::ErrorKind::NotFound)).into();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+        let err: std::io::Error = panicked::<std::io::Error>().into();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+        assert!(err.get_ref().unwrap().is::<TaskFailure>());
+    }
+
+    #[test]
+    fn test_into_anyhow() {
+        let err = AsyncWrapError::Inner(TestError(5)).into_anyhow();
+        assert_eq!(err.downcast_ref::<TestError>(), Some(&TestError(5)));
+
+        let err = AsyncWrapError::Inner(anyhow::anyhow!("already anyhow")).into_anyhow();
+        assert_eq!(err.to_string(), "already anyhow");
+
+        let err = panicked::<TestError>().into_anyhow();
+        assert!(err.downcast_ref::<TaskFailure>().unwrap().is_panic());
+    }
+
+    #[test]
+    fn test_into_report() {
+        let err = AsyncWrapError::Inner(TestError(6)).into_report();
+        assert_eq!(err.downcast_ref::<TestError>(), Some(&TestError(6)));
+
+        let err = panicked::<eyre::Report>().into_report();
+        assert_eq!(err.to_string(), "`AsyncClient::fetch` panicked: boom");
+    }
+}
+
+mod retry {
     use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use thiserror::Error;
 
-    struct VisService;
+    #[derive(Error, Debug, PartialEq)]
+    enum NetError {
+        #[error("timed out")]
+        Timeout,
+        #[error("refused")]
+        Refused,
+    }
 
-    #[blocking_impl(AsyncVisService)]
-    impl VisService {
-        #[async_wrap]
-        pub fn public_fn(&self) -> i32 {
-            1
+    fn is_transient(err: &NetError) -> bool {
+        *err == NetError::Timeout
+    }
+
+    struct FlakyClient {
+        calls: AtomicU32,
+        failures: u32,
+        error: NetError,
+    }
+
+    impl FlakyClient {
+        fn new(failures: u32, error: NetError) -> Self {
+            FlakyClient {
+                calls: AtomicU32::new(0),
+                failures,
+                error,
+            }
         }
 
-        #[async_wrap]
-        pub(crate) fn crate_fn(&self) -> i32 {
-            2
+        fn attempt(&self, payload: String) -> Result<String, NetError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if call <= self.failures {
+                Err(match self.error {
+                    NetError::Timeout => NetError::Timeout,
+                    NetError::Refused => NetError::Refused,
+                })
+            } else {
+                Ok(payload + &format!("@{call}"))
+            }
         }
     }
 
-    pub struct AsyncVisService {
-        inner: Arc<VisService>,
+    #[blocking_impl(AsyncFlakyClient)]
+    impl FlakyClient {
+        #[async_wrap(retry(max = 3, backoff = "exponential", base = "1ms", when = is_transient))]
+        pub fn send(&self, payload: String) -> Result<String, NetError> {
+            self.attempt(payload)
+        }
+
+        #[async_wrap(retry(max = 2, backoff = "fixed", base = "1ms"))]
+        pub fn send_any(&self, payload: String) -> Result<String, NetError> {
+            self.attempt(payload)
+        }
+    }
+
+    pub struct AsyncFlakyClient {
+        inner: Arc<FlakyClient>,
     }
 
     #[tokio::test]
-    async fn test_visibility_preserved() {
-        let svc = AsyncVisService {
-            inner: Arc::new(VisService),
+    async fn test_retries_until_success() {
+        let client = AsyncFlakyClient {
+            inner: Arc::new(FlakyClient::new(2, NetError::Timeout)),
         };
-        assert_eq!(svc.public_fn().await.unwrap(), 1);
-        assert_eq!(svc.crate_fn().await.unwrap(), 2);
+        assert_eq!(client.send("ping".to_string()).await.unwrap(), "ping@3");
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let client = AsyncFlakyClient {
+            inner: Arc::new(FlakyClient::new(5, NetError::Timeout)),
+        };
+        let err = client.send("ping".to_string()).await.unwrap_err();
+        assert_eq!(err.into_inner(), Some(NetError::Timeout));
+        assert_eq!(client.inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_skips_non_transient_errors() {
+        let client = AsyncFlakyClient {
+            inner: Arc::new(FlakyClient::new(1, NetError::Refused)),
+        };
+        let err = client.send("ping".to_string()).await.unwrap_err();
+        assert_eq!(err.into_inner(), Some(NetError::Refused));
+        assert_eq!(client.inner.calls.load(Ordering::SeqCst), 1);
+
+        assert_eq!(client.send_any("ping".to_string()).await.unwrap(), "ping@2");
+    }
+
+    struct InPlaceClient {
+        inner: FlakyClient,
+    }
+
+    #[blocking_impl(AsyncInPlaceClient, strategy = "block_in_place")]
+    impl InPlaceClient {
+        #[async_wrap(retry(max = 4, backoff = "linear", base = "1ms"))]
+        pub fn send(&self, payload: &str) -> Result<String, NetError> {
+            self.inner.attempt(payload.to_string())
+        }
+    }
+
+    pub struct AsyncInPlaceClient {
+        inner: InPlaceClient,
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_retry_block_in_place() {
+        let client = AsyncInPlaceClient {
+            inner: InPlaceClient {
+                inner: FlakyClient::new(3, NetError::Refused),
+            },
+        };
+        let result: Result<String, NetError> = client.send("ping").await;
+        assert_eq!(result.unwrap(), "ping@4");
+    }
+
+    #[test]
+    fn test_backoff_delays() {
+        use std::time::Duration;
+
+        let base = Duration::from_millis(50);
+        let exponential = asyncwrap::RetryPolicy::new(4, asyncwrap::Backoff::Exponential, base);
+        assert_eq!(exponential.delay(1), Duration::from_millis(50));
+        assert_eq!(exponential.delay(3), Duration::from_millis(200));
+        assert!(exponential.should_retry(3));
+        assert!(!exponential.should_retry(4));
+
+        let linear = asyncwrap::RetryPolicy::new(4, asyncwrap::Backoff::Linear, base);
+        assert_eq!(linear.delay(3), Duration::from_millis(150));
+
+        let fixed = asyncwrap::RetryPolicy::new(4, asyncwrap::Backoff::Fixed, base);
+        assert_eq!(fixed.delay(3), base);
     }
 }
 
-mod task_panic {
+mod circuit_breaker {
     use super::*;
+    use asyncwrap::CircuitBreaker;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
     use thiserror::Error;
 
-    #[derive(Error, Debug)]
-    #[error("inner error")]
-    struct InnerError;
+    #[derive(Error, Debug, PartialEq)]
+    #[error("device offline")]
+    struct Offline;
 
-    struct PanickingService;
+    struct Device {
+        online: AtomicBool,
+        calls: AtomicU32,
+    }
 
-    #[blocking_impl(AsyncPanickingService)]
-    impl PanickingService {
-        #[async_wrap]
-        pub fn will_panic(&self) -> i32 {
-            panic!("intentional panic for testing");
+    impl Device {
+        fn new(online: bool) -> Self {
+            Device {
+                online: AtomicBool::new(online),
+                calls: AtomicU32::new(0),
+            }
         }
+    }
 
+    #[blocking_impl(
+        AsyncDevice,
+        circuit_breaker(failure_threshold = 2, open_for = "50ms", half_open_probes = 1)
+    )]
+    impl Device {
         #[async_wrap]
-        #[allow(clippy::unnecessary_wraps, clippy::manual_assert)]
-        pub fn might_panic(&self, should_panic: bool) -> Result<i32, InnerError> {
-            if should_panic {
-                panic!("intentional panic for testing");
+        pub fn read(&self) -> Result<u32, Offline> {
+            let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if self.online.load(Ordering::SeqCst) {
+                Ok(calls)
+            } else {
+                Err(Offline)
             }
-            Ok(42)
+        }
+
+        #[async_wrap]
+        pub fn calls(&self) -> u32 {
+            self.calls.load(Ordering::SeqCst)
         }
     }
 
-    pub struct AsyncPanickingService {
-        inner: Arc<PanickingService>,
+    pub struct AsyncDevice {
+        inner: Arc<Device>,
+        breaker: CircuitBreaker,
+    }
+
+    fn offline_device() -> AsyncDevice {
+        AsyncDevice {
+            inner: Arc::new(Device::new(false)),
+            breaker: CircuitBreaker::new(),
+        }
     }
 
     #[tokio::test]
-    async fn test_panic_returns_join_error() {
-        let svc = AsyncPanickingService {
-            inner: Arc::new(PanickingService),
-        };
+    async fn test_opens_after_threshold() {
+        let device = offline_device();
+        assert_eq!(device.circuit_state(), asyncwrap::CircuitState::Closed);
+
+        assert_eq!(device.read().await.unwrap_err().into_inner(), Some(Offline));
+        assert_eq!(device.circuit_state(), asyncwrap::CircuitState::Closed);
+        assert_eq!(device.read().await.unwrap_err().into_inner(), Some(Offline));
+        assert_eq!(device.circuit_state(), asyncwrap::CircuitState::Open);
+
+        let err = device.read().await.unwrap_err();
+        assert!(err.is_circuit_open());
+        assert_eq!(err.call_site().unwrap().method(), "read");
+        assert_eq!(
+            err.to_string(),
+            "`AsyncDevice::read` rejected: circuit breaker is open"
+        );
+        assert_eq!(device.inner.calls.load(Ordering::SeqCst), 2);
+        assert!(device.calls().await.unwrap_err().is_circuit_open());
+    }
 
-        let result = svc.will_panic().await;
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(err.is_panic());
+    #[tokio::test]
+    async fn test_half_open_probe_closes_circuit() {
+        let device = offline_device();
+        let _ = device.read().await;
+        let _ = device.read().await;
+        assert_eq!(device.circuit_state(), asyncwrap::CircuitState::Open);
+
+        tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+        assert_eq!(device.circuit_state(), asyncwrap::CircuitState::HalfOpen);
+
+        device.inner.online.store(true, Ordering::SeqCst);
+        assert_eq!(device.read().await.unwrap(), 3);
+        assert_eq!(device.circuit_state(), asyncwrap::CircuitState::Closed);
     }
 
     #[tokio::test]
-    async fn test_result_panic_returns_task_failed() {
-        let svc = AsyncPanickingService {
-            inner: Arc::new(PanickingService),
+    async fn test_failed_probe_reopens_circuit() {
+        let device = offline_device();
+        let _ = device.read().await;
+        let _ = device.read().await;
+
+        tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+        assert_eq!(device.read().await.unwrap_err().into_inner(), Some(Offline));
+        assert_eq!(device.circuit_state(), asyncwrap::CircuitState::Open);
+        assert!(device.read().await.unwrap_err().is_circuit_open());
+    }
+
+    #[tokio::test]
+    async fn test_success_resets_failure_count() {
+        let device = offline_device();
+        let _ = device.read().await;
+        device.inner.online.store(true, Ordering::SeqCst);
+        assert!(device.read().await.is_ok());
+        device.inner.online.store(false, Ordering::SeqCst);
+        let _ = device.read().await;
+        assert_eq!(device.circuit_state(), asyncwrap::CircuitState::Closed);
+
+        device.breaker.reset();
+        assert_eq!(device.circuit_state(), asyncwrap::CircuitState::Closed);
+    }
+
+    struct Sensor;
+
+    #[blocking_impl(
+        AsyncSensor,
+        strategy = "block_in_place",
+        circuit_breaker(failure_threshold = 1, field = "health")
+    )]
+    impl Sensor {
+        #[async_wrap]
+        pub fn sample(&self, ok: bool) -> Result<u8, Offline> {
+            if ok {
+                Ok(1)
+            } else {
+                Err(Offline)
+            }
+        }
+    }
+
+    pub struct AsyncSensor {
+        inner: Sensor,
+        health: CircuitBreaker,
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_in_place_breaker() {
+        let sensor = AsyncSensor {
+            inner: Sensor,
+            health: CircuitBreaker::new(),
         };
+        assert_eq!(sensor.sample(true).await.unwrap(), 1);
+        assert_eq!(
+            sensor.sample(false).await.unwrap_err().into_inner(),
+            Some(Offline)
+        );
+        assert!(sensor.sample(true).await.unwrap_err().is_circuit_open());
+        assert_eq!(sensor.circuit_state(), asyncwrap::CircuitState::Open);
+    }
 
-        let result = svc.might_panic(true).await;
-        assert!(result.is_err());
-        assert!(matches!(
-            result.unwrap_err(),
-            asyncwrap::AsyncWrapError::TaskFailed(_)
+    #[test]
+    fn test_probe_of_an_earlier_half_open_phase_is_ignored() {
+        use asyncwrap::{CircuitBreakerConfig, CircuitState};
+        use std::time::Duration;
+
+        let config = CircuitBreakerConfig::new(1, Duration::from_millis(10), 2);
+        let breaker = CircuitBreaker::new();
+        breaker.try_acquire(&config).unwrap().record(false);
+        assert_eq!(breaker.state(&config), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(15));
+        let slow_probe = breaker.try_acquire(&config).unwrap();
+        breaker.try_acquire(&config).unwrap().record(false);
+        assert_eq!(breaker.state(&config), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(15));
+        assert_eq!(breaker.state(&config), CircuitState::HalfOpen);
+        slow_probe.record(true);
+        assert_eq!(breaker.state(&config), CircuitState::HalfOpen);
+
+        let probes = [breaker.try_acquire(&config), breaker.try_acquire(&config)];
+        assert!(probes.iter().all(Option::is_some));
+        assert!(breaker.try_acquire(&config).is_none());
+        for probe in probes.into_iter().flatten() {
+            probe.record(true);
+        }
+        assert_eq!(breaker.state(&config), CircuitState::Closed);
+    }
+}
+
+mod observer {
+    use asyncwrap::{blocking_impl, CallOutcome, CallSite, CallTiming, WrapObserver};
+    use std::sync::{Arc, Mutex};
+    use thiserror::Error;
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Start(String),
+        Spawned(String),
+        Panic(String, Option<String>),
+        Complete(String, CallOutcome),
+    }
+
+    struct Recorder {
+        events: Mutex<Vec<Event>>,
+    }
+
+    impl Recorder {
+        const fn new() -> Self {
+            Recorder {
+                events: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn take(&self) -> Vec<Event> {
+            std::mem::take(&mut *self.events.lock().unwrap())
+        }
+    }
+
+    impl WrapObserver for Recorder {
+        fn on_start(&self, call_site: CallSite) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(Event::Start(call_site.to_string()));
+        }
+
+        fn on_spawned(&self, call_site: CallSite, _queue_delay: std::time::Duration) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(Event::Spawned(call_site.to_string()));
+        }
+
+        fn on_complete(&self, call_site: CallSite, outcome: CallOutcome, timing: CallTiming) {
+            assert!(timing.total() >= timing.running);
+            self.events
+                .lock()
+                .unwrap()
+                .push(Event::Complete(call_site.to_string(), outcome));
+        }
+
+        fn on_panic(&self, call_site: CallSite, message: Option<&str>) {
+            self.events.lock().unwrap().push(Event::Panic(
+                call_site.to_string(),
+                message.map(str::to_owned),
+            ));
+        }
+    }
+
+    static STORE_EVENTS: Recorder = Recorder::new();
+    static CACHE_EVENTS: Recorder = Recorder::new();
+
+    #[derive(Error, Debug)]
+    #[error("missing key")]
+    struct Missing;
+
+    struct Store;
+
+    #[blocking_impl(AsyncStore, observer = STORE_EVENTS)]
+    impl Store {
+        #[async_wrap]
+        pub fn get(&self, key: u32) -> Result<u32, Missing> {
+            if key == 0 {
+                Err(Missing)
+            } else {
+                Ok(key * 2)
+            }
+        }
+
+        #[async_wrap]
+        pub fn crash(&self) -> u32 {
+            panic!("store crashed");
+        }
+    }
+
+    pub struct AsyncStore {
+        inner: Arc<Store>,
+    }
+
+    struct Cache;
+
+    #[blocking_impl(AsyncCache, strategy = "block_in_place", observer = CACHE_EVENTS)]
+    impl Cache {
+        #[async_wrap]
+        pub fn len(&self) -> usize {
+            3
+        }
+    }
+
+    pub struct AsyncCache {
+        inner: Cache,
+    }
+
+    // All events go through one static per type, so each test owns a separate wrapper type.
+    #[tokio::test]
+    async fn test_spawn_blocking_events() {
+        let store = AsyncStore {
+            inner: Arc::new(Store),
+        };
+        let site = "AsyncStore::get".to_owned();
+
+        assert_eq!(store.get(2).await.unwrap(), 4);
+        assert!(store.get(0).await.unwrap_err().inner().is_some());
+        assert!(store.crash().await.unwrap_err().is_panic());
+
+        let crash = "AsyncStore::crash".to_owned();
+        assert_eq!(
+            STORE_EVENTS.take(),
+            vec![
+                Event::Start(site.clone()),
+                Event::Spawned(site.clone()),
+                Event::Complete(site.clone(), CallOutcome::Ok),
+                Event::Start(site.clone()),
+                Event::Spawned(site.clone()),
+                Event::Complete(site, CallOutcome::Err),
+                Event::Start(crash.clone()),
+                Event::Spawned(crash.clone()),
+                Event::Panic(crash.clone(), Some("store crashed".to_owned())),
+                Event::Complete(crash, CallOutcome::Panicked),
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_in_place_events() {
+        let cache = AsyncCache { inner: Cache };
+        assert_eq!(cache.len().await, 3);
+
+        let site = "AsyncCache::len".to_owned();
+        assert_eq!(
+            CACHE_EVENTS.take(),
+            vec![
+                Event::Start(site.clone()),
+                Event::Spawned(site.clone()),
+                Event::Complete(site, CallOutcome::Ok),
+            ]
+        );
+    }
+}
+
+mod slow_calls {
+    use asyncwrap::{blocking_impl, SlowCall};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    static SLOW_CALLS: Mutex<Vec<SlowCall>> = Mutex::new(Vec::new());
+
+    fn record(call: &SlowCall) {
+        SLOW_CALLS.lock().unwrap().push(*call);
+    }
+
+    struct Disk;
+
+    #[blocking_impl(AsyncDisk, warn_after = "20ms", on_slow = record)]
+    impl Disk {
+        #[async_wrap]
+        pub fn read(&self, delay_ms: u64) -> u64 {
+            std::thread::sleep(Duration::from_millis(delay_ms));
+            delay_ms
+        }
+
+        #[async_wrap(warn_after = "1h")]
+        pub fn flush(&self, delay_ms: u64) -> u64 {
+            std::thread::sleep(Duration::from_millis(delay_ms));
+            delay_ms
+        }
+
+        #[async_wrap(panic_on_slow = true)]
+        pub fn sync(&self, delay_ms: u64) -> u64 {
+            std::thread::sleep(Duration::from_millis(delay_ms));
+            delay_ms
+        }
+    }
+
+    pub struct AsyncDisk {
+        inner: Arc<Disk>,
+    }
+
+    struct Cpu;
+
+    #[blocking_impl(AsyncCpu, strategy = "block_in_place")]
+    impl Cpu {
+        #[async_wrap(warn_after = "1ms", on_slow = asyncwrap::log_slow_call)]
+        pub fn spin(&self) -> u8 {
+            std::thread::sleep(Duration::from_millis(5));
+            1
+        }
+    }
+
+    pub struct AsyncCpu {
+        inner: Cpu,
+    }
+
+    #[tokio::test]
+    async fn test_slow_calls_reported() {
+        let disk = AsyncDisk {
+            inner: Arc::new(Disk),
+        };
+
+        assert_eq!(disk.read(0).await.unwrap(), 0);
+        assert_eq!(disk.read(40).await.unwrap(), 40);
+        assert_eq!(disk.flush(40).await.unwrap(), 40);
+
+        let calls = std::mem::take(&mut *SLOW_CALLS.lock().unwrap());
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].call_site().to_string(), "AsyncDisk::read");
+        assert_eq!(calls[0].threshold(), Duration::from_millis(20));
+        assert!(calls[0].elapsed() >= Duration::from_millis(40));
+
+        #[cfg(debug_assertions)]
+        {
+            let err = disk.sync(40).await.unwrap_err();
+            let message = err.panic_message().unwrap();
+            assert!(message.starts_with("`AsyncDisk::sync` blocked for"));
+        }
+        assert_eq!(disk.sync(0).await.unwrap(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_in_place_keeps_return_type() {
+        let cpu = AsyncCpu { inner: Cpu };
+        assert_eq!(cpu.spin().await, 1);
+    }
+}
+
+mod async_impl {
+    use asyncwrap::async_impl;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use thiserror::Error;
+    use tokio::runtime::{Builder, Handle, Runtime};
+
+    #[derive(Error, Debug, PartialEq)]
+    #[error("counter overflow")]
+    struct Overflow;
+
+    struct Counter {
+        value: AtomicU32,
+    }
+
+    #[async_impl(BlockingCounter)]
+    impl Counter {
+        /// Adds `n` to the counter.
+        #[blocking_wrap]
+        pub async fn add(&self, n: u32) -> Result<u32, Overflow> {
+            tokio::task::yield_now().await;
+            let previous = self.value.fetch_add(n, Ordering::SeqCst);
+            previous.checked_add(n).ok_or(Overflow)
+        }
+
+        #[blocking_wrap]
+        pub async fn get(&self) -> u32 {
+            tokio::task::yield_now().await;
+            self.value.load(Ordering::SeqCst)
+        }
+
+        #[blocking_wrap]
+        pub async fn reset(&self) {
+            tokio::task::yield_now().await;
+            self.value.store(0, Ordering::SeqCst);
+        }
+    }
+
+    pub struct BlockingCounter {
+        inner: Counter,
+        runtime: Runtime,
+    }
+
+    struct Store;
+
+    #[async_impl(BlockingStore, field = "store", runtime = "handle")]
+    impl Store {
+        #[blocking_wrap]
+        pub async fn echo(&self, key: &str) -> String {
+            tokio::task::yield_now().await;
+            key.to_owned()
+        }
+    }
+
+    pub struct BlockingStore {
+        store: Store,
+        handle: Handle,
+    }
+
+    fn counter() -> BlockingCounter {
+        BlockingCounter {
+            inner: Counter {
+                value: AtomicU32::new(0),
+            },
+            runtime: Builder::new_current_thread().build().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_owned_runtime() {
+        let counter = counter();
+        assert_eq!(counter.add(2).unwrap(), 2);
+        assert_eq!(counter.add(3).unwrap(), 5);
+        assert_eq!(counter.get().unwrap(), 5);
+        counter.reset().unwrap();
+        assert_eq!(counter.get().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_inner_error() {
+        let counter = counter();
+        counter.add(u32::MAX).unwrap();
+        assert_eq!(counter.add(1).unwrap_err().into_inner(), Some(Overflow));
+    }
+
+    #[test]
+    fn test_handle_from_another_thread() {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .build()
+            .unwrap();
+        let store = BlockingStore {
+            store: Store,
+            handle: runtime.handle().clone(),
+        };
+        let echoed = std::thread::spawn(move || store.echo("key").unwrap())
+            .join()
+            .unwrap();
+        assert_eq!(echoed, "key");
+    }
+
+    #[tokio::test]
+    async fn test_inside_runtime_is_rejected() {
+        let counter = tokio::task::spawn_blocking(counter).await.unwrap();
+        let err = counter.get().unwrap_err();
+        assert!(err.is_inside_runtime());
+        assert_eq!(
+            err.to_string(),
+            "`BlockingCounter::get` cannot block inside an async runtime; \
+             await the async method instead"
+        );
+        tokio::task::spawn_blocking(move || drop(counter))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_spawn_blocking_thread_is_rejected() {
+        let err = tokio::task::spawn_blocking(|| counter().get().unwrap_err())
+            .await
+            .unwrap();
+        assert!(err.is_inside_runtime());
+    }
+}
+
+mod free_functions {
+    use asyncwrap::{async_wrap, blocking_mod};
+    use thiserror::Error;
+
+    #[derive(Error, Debug, PartialEq)]
+    #[error("empty input")]
+    pub struct Empty;
+
+    /// Sums the bytes of an image.
+    #[async_wrap]
+    pub fn decode_image(bytes: Vec<u8>) -> Result<u32, Empty> {
+        if bytes.is_empty() {
+            return Err(Empty);
+        }
+        Ok(bytes.into_iter().map(u32::from).sum())
+    }
+
+    #[async_wrap(strategy = "block_in_place")]
+    fn checksum(bytes: &[u8]) -> u8 {
+        bytes.iter().fold(0, |acc, &b| acc ^ b)
+    }
+
+    #[async_wrap]
+    fn explode() {
+        panic!("boom");
+    }
+
+    #[blocking_mod(warn_after = "1h")]
+    pub mod codec {
+        use super::Empty;
+
+        #[async_wrap]
+        pub fn encode(value: u32) -> Result<Vec<u8>, Empty> {
+            if value == 0 {
+                return Err(Empty);
+            }
+            Ok(value.to_le_bytes().to_vec())
+        }
+
+        #[async_wrap(strategy = "block_in_place")]
+        fn version() -> u8 {
+            2
+        }
+
+        pub fn not_wrapped() -> u8 {
+            version()
+        }
+
+        pub async fn private_version() -> u8 {
+            r#async::version().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sibling_async_function() {
+        assert_eq!(decode_image_async(vec![1, 2, 3]).await.unwrap(), 6);
+        assert_eq!(
+            decode_image_async(Vec::new())
+                .await
+                .unwrap_err()
+                .into_inner(),
+            Some(Empty)
+        );
+        assert_eq!(decode_image(vec![4]).unwrap(), 4);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_in_place_borrows() {
+        let bytes = vec![1, 3];
+        assert_eq!(checksum_async(&bytes).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_call_site_uses_module_path() {
+        let err = explode_async().await.unwrap_err();
+        let call_site = err.call_site().unwrap();
+        assert_eq!(call_site.type_name(), module_path!());
+        assert_eq!(call_site.method(), "explode");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_submodule() {
+        assert_eq!(codec::r#async::encode(258).await.unwrap(), vec![2, 1, 0, 0]);
+        assert_eq!(codec::private_version().await, 2);
+        assert_eq!(codec::not_wrapped(), 2);
+    }
+}
+
+mod foreign {
+    use asyncwrap::wrap_foreign;
+    use std::sync::Arc;
+
+    /// Stands in for a type from another crate.
+    mod vendor {
+        use std::sync::Mutex;
+
+        #[derive(Debug, PartialEq)]
+        pub struct SqlError(pub String);
+
+        #[derive(Default)]
+        pub struct Connection {
+            rows: Mutex<Vec<String>>,
+        }
+
+        impl Connection {
+            pub fn execute(&self, sql: &str) -> Result<usize, SqlError> {
+                if sql.is_empty() {
+                    return Err(SqlError("empty statement".to_owned()));
+                }
+                let mut rows = self.rows.lock().unwrap();
+                rows.push(sql.to_owned());
+                Ok(rows.len())
+            }
+
+            pub fn row_count(&self) -> usize {
+                self.rows.lock().unwrap().len()
+            }
+
+            pub fn close(&self) {
+                panic!("connection already closed");
+            }
+        }
+    }
+
+    wrap_foreign! {
+        AsyncConn for vendor::Connection {
+            /// Executes a statement.
+            pub fn execute(&self, #[by_ref] sql: String) -> Result<usize, vendor::SqlError>;
+            pub fn row_count(&self) -> usize;
+        }
+    }
+
+    pub struct AsyncConn {
+        inner: Arc<vendor::Connection>,
+    }
+
+    wrap_foreign! {
+        LocalConn for vendor::Connection, strategy = "block_in_place", field = "conn" {
+            pub fn execute(&self, sql: &str) -> Result<usize, vendor::SqlError>;
+            #[async_wrap(catch_unwind = true)]
+            pub fn close(&self);
+        }
+    }
+
+    pub struct LocalConn {
+        conn: vendor::Connection,
+    }
+
+    #[tokio::test]
+    async fn test_spawn_blocking_foreign() {
+        let conn = AsyncConn {
+            inner: Arc::new(vendor::Connection::default()),
+        };
+        assert_eq!(conn.execute("CREATE TABLE t".to_owned()).await.unwrap(), 1);
+        assert_eq!(
+            conn.execute(String::new()).await.unwrap_err().into_inner(),
+            Some(vendor::SqlError("empty statement".to_owned()))
+        );
+        assert_eq!(conn.row_count().await.unwrap(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_in_place_foreign() {
+        let conn = LocalConn {
+            conn: vendor::Connection::default(),
+        };
+        assert_eq!(conn.execute("SELECT 1").await, Ok(1));
+
+        let err = conn.close().await.unwrap_err();
+        assert_eq!(err.panic_message(), Some("connection already closed"));
+        assert_eq!(err.call_site().unwrap().to_string(), "LocalConn::close");
+    }
+}
+
+mod extern_sdk {
+    #![allow(unsafe_code)]
+
+    use asyncwrap::wrap_extern;
+    use std::os::raw::c_int;
+    use std::sync::atomic::AtomicI32;
+
+    #[repr(C)]
+    pub struct CrHandle {
+        brightness: AtomicI32,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub struct SdkError(c_int);
+
+    impl std::fmt::Display for SdkError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "sdk error {}", self.0)
+        }
+    }
+
+    impl std::error::Error for SdkError {}
+
+    fn check_status(code: c_int) -> Result<(), SdkError> {
+        if code == 0 {
+            Ok(())
+        } else {
+            Err(SdkError(code))
+        }
+    }
+
+    /// Stands in for the bindgen module of a C SDK.
+    mod sys {
+        use super::CrHandle;
+        use std::os::raw::c_int;
+        use std::sync::atomic::Ordering;
+
+        pub unsafe extern "C" fn sdk_set_brightness(handle: *mut CrHandle, level: c_int) -> c_int {
+            if !(0..=100).contains(&level) {
+                return -22;
+            }
+            unsafe { (*handle).brightness.store(level, Ordering::SeqCst) };
+            0
+        }
+
+        pub unsafe extern "C" fn sdk_brightness(handle: *mut CrHandle, out: *mut c_int) -> c_int {
+            unsafe { *out = (*handle).brightness.load(Ordering::SeqCst) };
+            0
+        }
+
+        pub unsafe extern "C" fn sdk_reset(handle: *mut CrHandle) {
+            unsafe { (*handle).brightness.store(0, Ordering::SeqCst) };
+        }
+
+        pub unsafe extern "C" fn sdk_frame_count(handle: *mut CrHandle) -> u64 {
+            unsafe { u64::from((*handle).brightness.load(Ordering::SeqCst).unsigned_abs()) * 10 }
+        }
+
+        pub unsafe extern "C" fn sdk_brightness_ptr(handle: *mut CrHandle) -> *mut c_int {
+            unsafe { (*handle).brightness.as_ptr() }
+        }
+    }
+
+    wrap_extern! {
+        Camera, AsyncCamera, handle = CrHandle, code = c_int, check = check_status,
+        error = SdkError, prefix = "sdk_", sys = sys, pool = "cameras"
+        extern "C" {
+            pub fn sdk_set_brightness(handle: *mut CrHandle, level: c_int) -> c_int;
+            pub fn sdk_brightness(handle: *mut CrHandle, out: *mut c_int) -> c_int;
+            pub fn sdk_reset(handle: *mut CrHandle);
+            pub fn sdk_frame_count(handle: *mut CrHandle) -> u64;
+            pub fn sdk_brightness_ptr(handle: *mut CrHandle) -> *mut c_int;
+            pub fn sdk_version() -> c_int;
+        }
+    }
+
+    #[repr(C)]
+    pub struct Lamp {
+        on: AtomicI32,
+    }
+
+    /// Stands in for a C library linked into the binary.
+    mod lamp_library {
+        use super::Lamp;
+        use std::os::raw::c_int;
+        use std::sync::atomic::Ordering;
+
+        #[no_mangle]
+        pub unsafe extern "C" fn asyncwrap_test_lamp_toggle(lamp: *mut Lamp) -> c_int {
+            unsafe { (*lamp).on.fetch_xor(1, Ordering::SeqCst) };
+            0
+        }
+    }
+
+    wrap_extern! {
+        BlockingLamp, AsyncLamp, strategy = "block_in_place", handle = Lamp, code = c_int,
+        check = check_status, error = SdkError, field = "lamp", prefix = "asyncwrap_test_lamp_"
+        extern "C" {
+            fn asyncwrap_test_lamp_toggle(lamp: *mut Lamp) -> c_int;
+        }
+    }
+
+    fn camera_handle() -> *mut CrHandle {
+        Box::into_raw(Box::new(CrHandle {
+            brightness: AtomicI32::new(50),
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_async_extern_calls() {
+        use std::sync::atomic::Ordering;
+
+        let raw = camera_handle();
+        let camera = AsyncCamera::new(unsafe { Camera::from_raw(raw) });
+
+        camera.set_brightness(80).await.unwrap();
+        assert_eq!(
+            camera.set_brightness(200).await.unwrap_err().into_inner(),
+            Some(SdkError(-22))
+        );
+        assert_eq!(camera.frame_count().await.unwrap(), 800);
+        camera.reset().await.unwrap();
+        assert_eq!(unsafe { (*raw).brightness.load(Ordering::SeqCst) }, 0);
+
+        drop(camera);
+        drop(unsafe { Box::from_raw(raw) });
+    }
+
+    #[test]
+    fn test_pointer_arguments_are_unsafe() {
+        let raw = camera_handle();
+        let camera = unsafe { Camera::from_raw(raw) };
+        assert_eq!(camera.as_ptr(), raw);
+
+        let mut level = 0;
+        unsafe { camera.brightness(&raw mut level) }.unwrap();
+        assert_eq!(level, 50);
+        assert_eq!(unsafe { *camera.brightness_ptr() }, 50);
+
+        drop(unsafe { Box::from_raw(raw) });
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_declared_extern_block() {
+        use std::sync::atomic::Ordering;
+
+        let mut lamp = Lamp {
+            on: AtomicI32::new(0),
+        };
+        let wrapper = AsyncLamp::new(unsafe { BlockingLamp::from_raw(&raw mut lamp) });
+        assert_eq!(wrapper.toggle().await, Ok(()));
+        assert_eq!(lamp.on.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_build_script_helper() {
+        let bindings = r#"
+            pub type c_int = i32;
+            #[repr(C)]
+            pub struct CrHandle { _unused: [u8; 0] }
+            extern "C" {
+                pub fn sdk_connect(handle: *mut CrHandle, timeout: c_int) -> c_int;
+                pub fn other_library_call(value: c_int);
+            }
+        "#;
+        let generated = asyncwrap::build::ExternWrapper::new("Camera", "AsyncCamera")
+            .handle("CrHandle")
+            .code("c_int")
+            .check("crate::check_status")
+            .error("crate::SdkError")
+            .prefix("sdk_")
+            .sys("crate::sys")
+            .setting(r#"strategy = "block_in_place""#)
+            .generate(bindings)
+            .unwrap();
+
+        assert!(generated.starts_with(":: asyncwrap :: wrap_extern !"));
+        assert!(generated.contains("sdk_connect"));
+        assert!(generated.contains("sys = crate :: sys"));
+        assert!(!generated.contains("other_library_call"));
+
+        let pooled = asyncwrap::build::ExternWrapper::new("Camera", "AsyncCamera")
+            .handle("CrHandle")
+            .code("c_int")
+            .check("crate::check_status")
+            .error("crate::SdkError")
+            .setting(r#"pool = "cameras""#)
+            .generate(bindings)
+            .unwrap();
+        assert!(pooled.contains("pool = \"cameras\""));
+
+        let missing = asyncwrap::build::ExternWrapper::new("Camera", "AsyncCamera")
+            .generate(bindings)
+            .unwrap_err();
+        assert_eq!(missing.kind(), std::io::ErrorKind::InvalidInput);
+
+        let unsupported = asyncwrap::build::ExternWrapper::new("Camera", "AsyncCamera")
+            .handle("CrHandle")
+            .code("c_int")
+            .check("crate::check_status")
+            .error("crate::SdkError")
+            .setting(r#"handle = "runtime""#)
+            .generate(bindings)
+            .unwrap_err();
+        assert_eq!(unsupported.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(
+            unsupported.to_string(),
+            "`wrap_extern!` does not support the runtime `handle` option"
+        );
+    }
+}
+
+mod io_adapters {
+    use std::io::{self, Cursor, Read, Write};
+    use std::sync::{Arc, Mutex};
+
+    /// A reader that is interrupted before every successful read.
+    struct Flaky {
+        data: Cursor<Vec<u8>>,
+        interrupt: bool,
+    }
+
+    impl Read for Flaky {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            self.data.read(buf)
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Sink {
+        written: Arc<Mutex<Vec<u8>>>,
+        flushes: Arc<Mutex<usize>>,
+        broken: bool,
+    }
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.broken {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            *self.flushes.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_to_end() {
+        use asyncwrap::io::BlockingReader;
+        use tokio::io::AsyncReadExt;
+
+        let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        let mut reader = BlockingReader::with_capacity(100, Cursor::new(data.clone()));
+
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).await.unwrap();
+        assert_eq!(read, data);
+    }
+
+    #[tokio::test]
+    async fn test_buf_read_lines_with_interrupts() {
+        use asyncwrap::io::BlockingReader;
+        use tokio::io::AsyncBufReadExt;
+
+        let reader = BlockingReader::with_capacity(
+            4,
+            Flaky {
+                data: Cursor::new(b"first line\nsecond\n".to_vec()),
+                interrupt: false,
+            },
+        );
+
+        let mut lines = reader.lines();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "first line");
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "second");
+        assert!(lines.next_line().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_buffers_until_flush() {
+        use asyncwrap::io::BlockingWriter;
+        use tokio::io::AsyncWriteExt;
+
+        let sink = Sink::default();
+        let mut writer = BlockingWriter::with_capacity(8, sink.clone());
+
+        writer.write_all(b"hello").await.unwrap();
+        assert!(sink.written.lock().unwrap().is_empty());
+
+        writer.write_all(b", world").await.unwrap();
+        assert_eq!(&*sink.written.lock().unwrap(), b"hello, w");
+
+        writer.shutdown().await.unwrap();
+        assert_eq!(&*sink.written.lock().unwrap(), b"hello, world");
+        assert_eq!(*sink.flushes.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_write_error_is_reported() {
+        use asyncwrap::io::BlockingWriter;
+        use tokio::io::AsyncWriteExt;
+
+        let sink = Sink {
+            broken: true,
+            ..Sink::default()
+        };
+        let mut writer = BlockingWriter::new(sink);
+
+        writer.write_all(b"data").await.unwrap();
+        let err = writer.flush().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[tokio::test]
+    async fn test_futures_io_traits() {
+        use asyncwrap::io::{BlockingReader, BlockingWriter};
+        use futures_io::{AsyncRead, AsyncWrite};
+        use std::future::poll_fn;
+        use std::pin::Pin;
+
+        let sink = Sink::default();
+        let mut writer = BlockingWriter::new(sink.clone());
+        let written = poll_fn(|cx| AsyncWrite::poll_write(Pin::new(&mut writer), cx, b"abc"))
+            .await
+            .unwrap();
+        assert_eq!(written, 3);
+        poll_fn(|cx| AsyncWrite::poll_close(Pin::new(&mut writer), cx))
+            .await
+            .unwrap();
+        assert_eq!(&*sink.written.lock().unwrap(), b"abc");
+
+        let mut reader = BlockingReader::new(Cursor::new(b"xyz".to_vec()));
+        let mut buf = [0; 8];
+        let n = poll_fn(|cx| AsyncRead::poll_read(Pin::new(&mut reader), cx, &mut buf))
+            .await
+            .unwrap();
+        assert_eq!(&buf[..n], b"xyz");
+    }
+}
+
+mod subscriptions {
+    use asyncwrap::blocking_impl;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    type Listener = Box<dyn Fn(Reading) + Send + Sync>;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Reading(u32);
+
+    #[derive(Debug, PartialEq)]
+    pub struct SensorError;
+
+    #[derive(Default)]
+    struct Sensor {
+        listeners: Mutex<HashMap<u64, Listener>>,
+        next_id: AtomicU64,
+        offline: bool,
+    }
+
+    #[blocking_impl(AsyncSensor)]
+    impl Sensor {
+        #[async_wrap(subscribe(unsubscribe = "unsubscribe", capacity = 4))]
+        pub fn subscribe(&self, callback: Box<dyn Fn(Reading) + Send + Sync>) -> u64 {
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+            self.listeners.lock().unwrap().insert(id, callback);
+            id
+        }
+
+        #[async_wrap(subscribe(capacity = 2, overflow = "drop_newest"))]
+        pub fn watch(
+            &self,
+            callback: impl Fn(&Reading) + Send + Sync + 'static,
+        ) -> Result<(), SensorError> {
+            if self.offline {
+                return Err(SensorError);
+            }
+            self.subscribe(Box::new(move |reading| callback(&reading)));
+            Ok(())
+        }
+
+        #[async_wrap(subscribe(unsubscribe = "unsubscribe", capacity = 1, overflow = "block"))]
+        pub fn follow(&self, callback: Box<dyn Fn(Reading) + Send + Sync>) -> u64 {
+            self.subscribe(callback)
+        }
+
+        pub fn unsubscribe(&self, id: u64) {
+            self.listeners.lock().unwrap().remove(&id);
+        }
+
+        fn emit(&self, value: u32) {
+            for listener in self.listeners.lock().unwrap().values() {
+                listener(Reading(value));
+            }
+        }
+
+        fn listener_count(&self) -> usize {
+            self.listeners.lock().unwrap().len()
+        }
+    }
+
+    pub struct AsyncSensor {
+        inner: Arc<Sensor>,
+    }
+
+    struct Gauge {
+        sensor: Sensor,
+    }
+
+    #[blocking_impl(AsyncGauge, strategy = "block_in_place")]
+    impl Gauge {
+        #[async_wrap(subscribe(unsubscribe = "unsubscribe"))]
+        pub fn subscribe(&self, callback: Box<dyn Fn(Reading) + Send + Sync>) -> u64 {
+            self.sensor.subscribe(callback)
+        }
+
+        pub fn unsubscribe(&self, id: u64) {
+            self.sensor.unsubscribe(id);
+        }
+    }
+
+    pub struct AsyncGauge {
+        inner: Gauge,
+    }
+
+    fn sensor() -> AsyncSensor {
+        AsyncSensor {
+            inner: Arc::new(Sensor::default()),
+        }
+    }
+
+    async fn wait_for_listeners(sensor: &Sensor, count: usize) {
+        for _ in 0..100 {
+            if sensor.listener_count() == count {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!(
+            "expected {count} listeners, found {}",
+            sensor.listener_count()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_events_until_dropped() {
+        let sensor = sensor();
+        let mut readings = sensor.subscribe().await.unwrap();
+        assert_eq!(sensor.inner.listener_count(), 1);
+
+        sensor.inner.emit(1);
+        sensor.inner.emit(2);
+        assert_eq!(readings.recv().await, Some(Reading(1)));
+        assert_eq!(readings.recv().await, Some(Reading(2)));
+
+        drop(readings);
+        wait_for_listeners(&sensor.inner, 0).await;
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest() {
+        let sensor = sensor();
+        let mut readings = sensor.subscribe().await.unwrap();
+
+        for value in 1..=6 {
+            sensor.inner.emit(value);
+        }
+        for value in 3..=6 {
+            assert_eq!(readings.recv().await, Some(Reading(value)));
+        }
+        assert_eq!(readings.dropped(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_drop_newest_and_errors() {
+        let sensor = sensor();
+        let mut readings = sensor.watch().await.unwrap();
+
+        for value in 1..=3 {
+            sensor.inner.emit(value);
+        }
+        assert_eq!(readings.recv().await, Some(Reading(1)));
+        assert_eq!(readings.recv().await, Some(Reading(2)));
+        assert_eq!(readings.dropped(), 1);
+
+        let offline = AsyncSensor {
+            inner: Arc::new(Sensor {
+                offline: true,
+                ..Sensor::default()
+            }),
+        };
+        let err = offline.watch().await.unwrap_err();
+        assert!(matches!(err, asyncwrap::AsyncWrapError::Inner(SensorError)));
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_room() {
+        let sensor = sensor();
+        let mut readings = sensor.follow().await.unwrap();
+
+        let emitter = {
+            let sensor = Arc::clone(&sensor.inner);
+            std::thread::spawn(move || (1..=3).for_each(|value| sensor.emit(value)))
+        };
+        for value in 1..=3 {
+            assert_eq!(readings.recv().await, Some(Reading(value)));
+        }
+        emitter.join().unwrap();
+        assert_eq!(readings.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stream_ends_with_callback() {
+        use futures_core::Stream;
+        use std::future::poll_fn;
+        use std::pin::Pin;
+
+        let sensor = sensor();
+        let mut readings = sensor.subscribe().await.unwrap();
+        sensor.inner.emit(7);
+        sensor.inner.listeners.lock().unwrap().clear();
+
+        let first = poll_fn(|cx| Pin::new(&mut readings).poll_next(cx)).await;
+        assert_eq!(first, Some(Reading(7)));
+        let end = poll_fn(|cx| Pin::new(&mut readings).poll_next(cx)).await;
+        assert_eq!(end, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_in_place_unsubscribes_on_drop() {
+        let gauge = AsyncGauge {
+            inner: Gauge {
+                sensor: Sensor::default(),
+            },
+        };
+
+        let mut readings = gauge.subscribe().await;
+        gauge.inner.sensor.emit(5);
+        assert_eq!(readings.recv().await, Some(Reading(5)));
+
+        drop(readings);
+        assert_eq!(gauge.inner.sensor.listener_count(), 0);
+    }
+}
+
+mod progress {
+    use asyncwrap::{async_wrap, blocking_impl, Progress};
+    use std::sync::{mpsc, Arc};
+
+    struct Device;
+
+    #[blocking_impl(AsyncDevice)]
+    impl Device {
+        /// Writes `image`, returning the bytes read back.
+        #[async_wrap(retry(max = 2))]
+        pub fn upload(
+            &self,
+            image: Vec<u8>,
+            progress: &asyncwrap::Progress,
+        ) -> Result<Vec<u8>, String> {
+            if image.is_empty() {
+                return Err("empty image".to_string());
+            }
+            progress.set_total(image.len() as u64);
+            for chunk in image.chunks(4) {
+                progress.advance(chunk.len() as u64);
+            }
+            progress.set_message("verifying");
+            Ok(image)
+        }
+
+        #[async_wrap]
+        pub fn flash(&self, progress: &asyncwrap::Progress, steps: mpsc::Receiver<()>) -> u64 {
+            for () in steps {
+                progress.advance(1);
+            }
+            progress.current().completed * 10
+        }
+    }
+
+    pub struct AsyncDevice {
+        inner: Arc<Device>,
+    }
+
+    #[async_wrap]
+    fn checksum(data: Vec<u8>, progress: asyncwrap::Progress) -> (u32, Progress) {
+        progress.set_total(data.len() as u64);
+        progress.set_completed(data.len() as u64);
+        (data.into_iter().map(u32::from).sum(), progress)
+    }
+
+    fn device() -> AsyncDevice {
+        AsyncDevice {
+            inner: Arc::new(Device),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_final_progress() {
+        let device = device();
+        let upload = device.upload(vec![0; 10]);
+        let progress = upload.progress();
+
+        assert_eq!(upload.await.unwrap(), vec![0; 10]);
+        let state = progress.borrow().clone();
+        assert_eq!(
+            state,
+            asyncwrap::ProgressState {
+                completed: 10,
+                total: Some(10),
+                message: Some("verifying".to_string()),
+            }
+        );
+        assert_eq!(state.fraction(), Some(1.0));
+    }
+
+    #[tokio::test]
+    async fn test_progress_while_running() {
+        let device = device();
+        let (step, steps) = mpsc::channel();
+        let flash = device.flash(steps);
+        let mut progress = flash.progress();
+
+        let watch = async move {
+            for expected in 1..=3 {
+                step.send(()).unwrap();
+                let state = progress
+                    .wait_for(|state| state.completed == expected)
+                    .await
+                    .unwrap();
+                assert_eq!(state.total, None);
+                assert_eq!(state.fraction(), None);
+            }
+        };
+        let (result, ()) = tokio::join!(flash, watch);
+        assert_eq!(result.unwrap(), 30);
+    }
+
+    #[tokio::test]
+    async fn test_free_function_and_blocking_callers() {
+        let checksum = checksum_async(vec![1, 2, 3]);
+        let progress = checksum.progress();
+        let (sum, handle) = checksum.await.unwrap();
+        assert_eq!(sum, 6);
+        assert_eq!(progress.borrow().fraction(), Some(1.0));
+        assert_eq!(handle.current(), *progress.borrow());
+
+        let (handle, progress) = Progress::channel();
+        assert_eq!(Device.upload(vec![0; 6], &handle).unwrap().len(), 6);
+        assert_eq!(progress.borrow().completed, 6);
+        assert!(Device.upload(Vec::new(), &Progress::default()).is_err());
+    }
+
+    mod own_progress_type {
+        use asyncwrap::blocking_impl;
+        use std::sync::Arc;
+
+        /// Not `asyncwrap::Progress`, so it stays an argument.
+        #[derive(Clone, Copy)]
+        pub struct Progress(pub u32);
+
+        struct Tracker;
+
+        #[blocking_impl(AsyncTracker)]
+        impl Tracker {
+            #[async_wrap]
+            pub fn percent(&self, progress: Progress) -> u32 {
+                progress.0
+            }
+        }
+
+        pub struct AsyncTracker {
+            inner: Arc<Tracker>,
+        }
+
+        #[tokio::test]
+        async fn test_other_progress_types_are_arguments() {
+            let tracker = AsyncTracker {
+                inner: Arc::new(Tracker),
+            };
+            assert_eq!(tracker.percent(Progress(40)).await.unwrap(), 40);
+        }
+    }
+}
+
+mod coalesce {
+    use asyncwrap::{blocking_impl, WrapState};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct ConfigStore {
+        reads: AtomicUsize,
+    }
+
+    #[blocking_impl(AsyncConfigStore, state = "flights")]
+    impl ConfigStore {
+        #[async_wrap(coalesce)]
+        pub fn get_config(&self, device_id: u32) -> Result<String, String> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            if device_id == 0 {
+                return Err("unknown device".to_string());
+            }
+            Ok(format!("config-{device_id}"))
+        }
+
+        #[async_wrap(coalesce)]
+        pub fn reset(&self) {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            panic!("reset failed");
+        }
+    }
+
+    pub struct AsyncConfigStore {
+        inner: Arc<ConfigStore>,
+        flights: WrapState,
+    }
+
+    fn store() -> Arc<AsyncConfigStore> {
+        Arc::new(AsyncConfigStore {
+            inner: Arc::new(ConfigStore::default()),
+            flights: WrapState::new(),
+        })
+    }
+
+    fn reads(store: &AsyncConfigStore) -> usize {
+        store.inner.reads.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_identical_calls_share_one_blocking_call() {
+        let store = store();
+        let tasks: Vec<_> = [1, 1, 1, 1, 2, 2, 0, 0]
+            .into_iter()
+            .map(|device_id| {
+                let store = Arc::clone(&store);
+                tokio::spawn(async move { store.get_config(device_id).await })
+            })
+            .collect();
+
+        let mut results = Vec::new();
+        for task in tasks {
+            results.push(task.await.unwrap());
+        }
+        assert_eq!(reads(&store), 3);
+        assert!(results[..4]
+            .iter()
+            .all(|r| r.as_ref().is_ok_and(|c| c == "config-1")));
+        assert!(results[4..6]
+            .iter()
+            .all(|r| r.as_ref().is_ok_and(|c| c == "config-2")));
+        assert!(results[6..].iter().all(
+            |r| matches!(r, Err(asyncwrap::AsyncWrapError::Inner(e)) if e == "unknown device")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_later_calls_run_again() {
+        let store = store();
+        assert_eq!(store.get_config(1).await.unwrap(), "config-1");
+        assert_eq!(store.get_config(1).await.unwrap(), "config-1");
+        assert_eq!(reads(&store), 2);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_waiter_does_not_cancel_others() {
+        let store = store();
+        let first = tokio::time::timeout(Duration::from_millis(5), store.get_config(1));
+        let (first, second) = tokio::join!(first, store.get_config(1));
+
+        assert!(first.is_err());
+        assert_eq!(second.unwrap(), "config-1");
+        assert_eq!(reads(&store), 1);
+    }
+
+    #[tokio::test]
+    async fn test_panics_reach_every_waiter() {
+        let store = store();
+        let (first, second) = tokio::join!(store.reset(), store.reset());
+
+        assert_eq!(reads(&store), 1);
+        for result in [first, second] {
+            let err = result.unwrap_err();
+            assert_eq!(err.panic_message(), Some("reset failed"));
+            assert_eq!(err.call_site().unwrap().method(), "reset");
+        }
+    }
+
+    mod observed {
+        use asyncwrap::{
+            blocking_impl, CallOutcome, CallSite, CallTiming, WrapObserver, WrapState,
+        };
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+        use tokio::runtime::Handle;
+
+        struct Outcomes(Mutex<Vec<(CallOutcome, Duration)>>);
+
+        impl WrapObserver for Outcomes {
+            fn on_complete(&self, _: CallSite, outcome: CallOutcome, timing: CallTiming) {
+                self.0.lock().unwrap().push((outcome, timing.queued));
+            }
+        }
+
+        static OUTCOMES: Outcomes = Outcomes(Mutex::new(Vec::new()));
+
+        struct Loader;
+
+        #[blocking_impl(AsyncLoader, observer = OUTCOMES, handle = "runtime")]
+        impl Loader {
+            #[async_wrap(coalesce)]
+            pub fn load(&self, id: u32) -> u32 {
+                std::thread::sleep(Duration::from_millis(50));
+                id
+            }
+        }
+
+        pub struct AsyncLoader {
+            inner: Arc<Loader>,
+            state: WrapState,
+            runtime: Handle,
+        }
+
+        #[tokio::test]
+        async fn test_queue_delay_and_cancellation_are_observed() {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .max_blocking_threads(1)
+                .build()
+                .unwrap();
+            let loader = Arc::new(AsyncLoader {
+                inner: Arc::new(Loader),
+                state: WrapState::new(),
+                runtime: runtime.handle().clone(),
+            });
+
+            let (first, second) = tokio::join!(loader.load(1), loader.load(2));
+            assert_eq!((first.unwrap(), second.unwrap()), (1, 2));
+            let queued = std::mem::take(&mut *OUTCOMES.0.lock().unwrap());
+            assert_eq!(queued.len(), 2);
+            assert!(queued
+                .iter()
+                .all(|(outcome, _)| *outcome == CallOutcome::Ok));
+            assert!(queued
+                .iter()
+                .any(|(_, delay)| *delay >= Duration::from_millis(40)));
+
+            // A runtime that shut down cancels the call before it runs.
+            runtime.shutdown_background();
+            assert!(loader.load(3).await.unwrap_err().is_cancelled());
+            let outcomes = OUTCOMES.0.lock().unwrap();
+            assert_eq!(outcomes.len(), 1);
+            assert_eq!(outcomes[0].0, CallOutcome::Cancelled);
+        }
+    }
+}
+
+mod cache {
+    use asyncwrap::{blocking_impl, WrapState};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct Inventory {
+        reads: AtomicUsize,
+    }
+
+    #[blocking_impl(AsyncInventory, strategy = "block_in_place")]
+    impl Inventory {
+        #[async_wrap(cache(ttl = "50ms", capacity = 2))]
+        pub fn stock(&self, sku: u32) -> Result<u32, String> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            if sku == 0 {
+                return Err("unknown sku".to_string());
+            }
+            Ok(sku * 10)
+        }
+
+        #[async_wrap(cache)]
+        pub fn warehouses(&self) -> Vec<String> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            vec!["north".to_string(), "south".to_string()]
+        }
+    }
+
+    pub struct AsyncInventory {
+        inner: Inventory,
+        state: WrapState,
+    }
+
+    fn inventory() -> AsyncInventory {
+        AsyncInventory {
+            inner: Inventory::default(),
+            state: WrapState::new(),
+        }
+    }
+
+    fn reads(inventory: &AsyncInventory) -> usize {
+        inventory.inner.reads.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_hits_skip_the_blocking_call() {
+        let inventory = inventory();
+        assert_eq!(inventory.stock(3).await, Ok(30));
+        assert_eq!(inventory.stock(3).await, Ok(30));
+        assert_eq!(inventory.stock(4).await, Ok(40));
+        assert_eq!(reads(&inventory), 2);
+
+        assert_eq!(inventory.warehouses().await.len(), 2);
+        assert_eq!(inventory.warehouses().await.len(), 2);
+        assert_eq!(reads(&inventory), 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_errors_are_not_cached() {
+        let inventory = inventory();
+        assert!(inventory.stock(0).await.is_err());
+        assert!(inventory.stock(0).await.is_err());
+        assert_eq!(reads(&inventory), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_entries_expire_after_ttl() {
+        let inventory = inventory();
+        inventory.stock(3).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+        inventory.stock(3).await.unwrap();
+        assert_eq!(reads(&inventory), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_capacity_evicts_oldest() {
+        let inventory = inventory();
+        for sku in [1, 2, 3, 2, 1] {
+            inventory.stock(sku).await.unwrap();
+        }
+        // 1 was evicted by 3; 2 was still cached.
+        assert_eq!(reads(&inventory), 4);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_invalidate_and_clear() {
+        let inventory = inventory();
+        inventory.stock(3).await.unwrap();
+        assert_eq!(inventory.warehouses().await.len(), 2);
+
+        inventory.invalidate_stock();
+        inventory.stock(3).await.unwrap();
+        assert_eq!(inventory.warehouses().await.len(), 2);
+        assert_eq!(reads(&inventory), 3);
+
+        inventory.clear_cache();
+        inventory.stock(3).await.unwrap();
+        assert_eq!(inventory.warehouses().await.len(), 2);
+        assert_eq!(reads(&inventory), 5);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_blocking_results_are_cached() {
+        use std::sync::Arc;
+
+        struct Clock(AtomicUsize);
+
+        #[blocking_impl(AsyncClock)]
+        impl Clock {
+            #[async_wrap(cache)]
+            pub fn tick(&self) -> usize {
+                self.0.fetch_add(1, Ordering::SeqCst)
+            }
+        }
+
+        struct AsyncClock {
+            inner: Arc<Clock>,
+            state: WrapState,
+        }
+
+        let clock = AsyncClock {
+            inner: Arc::new(Clock(AtomicUsize::new(0))),
+            state: WrapState::new(),
+        };
+        assert_eq!(clock.tick().await.unwrap(), 0);
+        assert_eq!(clock.tick().await.unwrap(), 0);
+        clock.invalidate_tick();
+        assert_eq!(clock.tick().await.unwrap(), 1);
+    }
+}
+
+mod batch {
+    use asyncwrap::{blocking_impl, WrapState};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Database {
+        batches: Mutex<Vec<Vec<u32>>>,
+    }
+
+    #[blocking_impl(AsyncDatabase)]
+    impl Database {
+        #[async_wrap(batch(with = get_many, max_size = 4, max_delay = "20ms"))]
+        pub fn get(&self, id: u32) -> Option<String> {
+            self.get_many(vec![id]).pop().flatten()
+        }
+
+        pub fn get_many(&self, ids: Vec<u32>) -> Vec<Option<String>> {
+            let rows = ids
+                .iter()
+                .map(|&id| (id != 0).then(|| format!("row-{id}")))
+                .collect();
+            self.batches.lock().unwrap().push(ids);
+            rows
+        }
+
+        #[async_wrap(batch(with = broken_many))]
+        pub fn broken(&self, id: u32) -> u32 {
+            id
+        }
+
+        pub fn broken_many(&self, mut ids: Vec<u32>) -> Vec<u32> {
+            ids.pop();
+            ids
+        }
+    }
+
+    pub struct AsyncDatabase {
+        inner: Arc<Database>,
+        state: WrapState,
+    }
+
+    fn database() -> Arc<AsyncDatabase> {
+        Arc::new(AsyncDatabase {
+            inner: Arc::new(Database::default()),
+            state: WrapState::new(),
+        })
+    }
+
+    fn batches(database: &AsyncDatabase) -> Vec<Vec<u32>> {
+        database.inner.batches.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_calls_share_one_batch() {
+        let database = database();
+        let (a, b, c) = tokio::join!(database.get(1), database.get(0), database.get(3));
+
+        assert_eq!(a.unwrap().as_deref(), Some("row-1"));
+        assert_eq!(b.unwrap(), None);
+        assert_eq!(c.unwrap().as_deref(), Some("row-3"));
+        assert_eq!(batches(&database), [vec![1, 0, 3]]);
+    }
+
+    #[tokio::test]
+    async fn test_full_batches_run_without_waiting() {
+        let database = database();
+        let started = std::time::Instant::now();
+        let results = tokio::join!(
+            database.get(1),
+            database.get(2),
+            database.get(3),
+            database.get(4)
+        );
+
+        assert!(started.elapsed() < std::time::Duration::from_millis(20));
+        assert_eq!(results.3.unwrap().as_deref(), Some("row-4"));
+        assert_eq!(batches(&database), [vec![1, 2, 3, 4]]);
+    }
+
+    #[tokio::test]
+    async fn test_batches_split_at_max_size() {
+        let database = database();
+        let results = tokio::join!(
+            database.get(1),
+            database.get(2),
+            database.get(3),
+            database.get(4),
+            database.get(5),
+            database.get(6)
+        );
+
+        assert_eq!(results.5.unwrap().as_deref(), Some("row-6"));
+        assert_eq!(batches(&database), [vec![1, 2, 3, 4], vec![5, 6]]);
+    }
+
+    #[tokio::test]
+    async fn test_missing_results_fail_the_batch() {
+        let database = database();
+        let (a, b) = tokio::join!(database.broken(1), database.broken(2));
+
+        for result in [a, b] {
+            let err = result.unwrap_err();
+            assert_eq!(
+                err.panic_message(),
+                Some("batch method returned 1 results for 2 arguments")
+            );
+            assert_eq!(err.call_site().unwrap().method(), "broken");
+        }
+    }
+
+    mod observed {
+        use asyncwrap::{
+            blocking_impl, CallOutcome, CallSite, CallTiming, CircuitBreaker, SlowCall,
+            WrapObserver, WrapState,
+        };
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        struct Completions(Mutex<Vec<(&'static str, CallOutcome)>>);
+
+        impl WrapObserver for Completions {
+            fn on_complete(&self, call_site: CallSite, outcome: CallOutcome, _: CallTiming) {
+                self.0.lock().unwrap().push((call_site.method(), outcome));
+            }
+        }
+
+        static COMPLETIONS: Completions = Completions(Mutex::new(Vec::new()));
+        static SLOW_CALLS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+        fn record_slow(call: &SlowCall) {
+            SLOW_CALLS.lock().unwrap().push(call.call_site().method());
+        }
+
+        struct Sensors;
+
+        #[blocking_impl(
+            AsyncSensors,
+            observer = COMPLETIONS,
+            warn_after = "10ms",
+            on_slow = record_slow,
+            circuit_breaker(failure_threshold = 2, open_for = "1h")
+        )]
+        impl Sensors {
+            #[async_wrap(batch(with = read_many))]
+            pub fn read(&self, id: u32) -> Result<u32, String> {
+                self.read_many(vec![id]).remove(0)
+            }
+
+            pub fn read_many(&self, ids: Vec<u32>) -> Vec<Result<u32, String>> {
+                std::thread::sleep(Duration::from_millis(20));
+                let read = |id| {
+                    if id == 0 {
+                        Err("no sensor".to_string())
+                    } else {
+                        Ok(id)
+                    }
+                };
+                ids.into_iter().map(read).collect()
+            }
+
+            #[async_wrap(batch(with = reset_many))]
+            pub fn reset(&self, id: u32) -> u32 {
+                id
+            }
+
+            pub fn reset_many(&self, _ids: Vec<u32>) -> Vec<u32> {
+                panic!("sensor bus crashed");
+            }
+        }
+
+        pub struct AsyncSensors {
+            inner: Arc<Sensors>,
+            state: WrapState,
+            breaker: CircuitBreaker,
+        }
+
+        #[tokio::test]
+        async fn test_batches_are_observed_and_guarded() {
+            let sensors = AsyncSensors {
+                inner: Arc::new(Sensors),
+                state: WrapState::new(),
+                breaker: CircuitBreaker::new(),
+            };
+
+            let (a, b) = tokio::join!(sensors.read(1), sensors.read(0));
+            assert_eq!(a.unwrap(), 1);
+            assert!(b.is_err());
+            assert_eq!(*COMPLETIONS.0.lock().unwrap(), [("read", CallOutcome::Err)]);
+            assert_eq!(*SLOW_CALLS.lock().unwrap(), ["read"]);
+
+            let (a, b) = tokio::join!(sensors.reset(1), sensors.reset(2));
+            assert!(a.unwrap_err().is_panic() && b.unwrap_err().is_panic());
+            assert_eq!(
+                COMPLETIONS.0.lock().unwrap().last(),
+                Some(&("reset", CallOutcome::Panicked))
+            );
+            assert_eq!(sensors.circuit_state(), asyncwrap::CircuitState::Open);
+            assert!(sensors.read(1).await.unwrap_err().is_circuit_open());
+        }
+    }
+}
+
+mod pools {
+    use asyncwrap::blocking_impl;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    static STARTED: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Default)]
+    struct Device {
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+    }
+
+    #[blocking_impl(AsyncDevice, pool = "devices")]
+    impl Device {
+        #[async_wrap]
+        pub fn read(&self) -> String {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20));
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            std::thread::current()
+                .name()
+                .unwrap_or_default()
+                .to_string()
+        }
+    }
+
+    pub struct AsyncDevice {
+        inner: Arc<Device>,
+    }
+
+    struct Sensor;
+
+    #[blocking_impl(AsyncSensor, pool = "sensors")]
+    impl Sensor {
+        #[async_wrap]
+        pub fn thread_name(&self) -> String {
+            std::thread::current()
+                .name()
+                .unwrap_or_default()
+                .to_string()
+        }
+    }
+
+    pub struct AsyncSensor {
+        inner: Arc<Sensor>,
+    }
+
+    #[tokio::test]
+    async fn test_calls_run_on_the_registered_pool() {
+        use asyncwrap::{PoolBuilder, PoolError};
+
+        PoolBuilder::new("devices")
+            .size(2)
+            .thread_name_prefix("device-io")
+            .stack_size(256 * 1024)
+            .on_thread_start(|| {
+                STARTED.fetch_add(1, Ordering::SeqCst);
+            })
+            .register()
+            .unwrap();
+
+        let device = AsyncDevice {
+            inner: Arc::new(Device::default()),
+        };
+        let names = tokio::join!(device.read(), device.read(), device.read(), device.read());
+
+        for name in [names.0, names.1, names.2, names.3] {
+            assert!(name.unwrap().starts_with("device-io-"));
+        }
+        assert_eq!(device.inner.max_running.load(Ordering::SeqCst), 2);
+        assert!(STARTED.load(Ordering::SeqCst) >= 1);
+        assert!(matches!(
+            PoolBuilder::new("devices").register(),
+            Err(PoolError::AlreadyRegistered(name)) if name == "devices"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unregistered_pools_start_with_defaults() {
+        let sensor = AsyncSensor {
+            inner: Arc::new(Sensor),
+        };
+        assert!(sensor.thread_name().await.unwrap().starts_with("sensors-"));
+        assert!(asyncwrap::PoolBuilder::new("sensors").register().is_err());
+    }
+
+    #[test]
+    fn test_zero_size_is_rejected() {
+        use asyncwrap::{PoolBuilder, PoolError};
+
+        let err = PoolBuilder::new("empty").size(0).register().unwrap_err();
+        assert!(matches!(&err, PoolError::ZeroSize(name) if name == "empty"));
+        assert_eq!(
+            err.to_string(),
+            "blocking pool \"empty\" must have at least one thread"
+        );
+        PoolBuilder::new("empty").size(1).register().unwrap();
+    }
+}
+
+mod thread_hooks {
+    use asyncwrap::blocking_impl;
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    static ATTACHES: AtomicUsize = AtomicUsize::new(0);
+    static DETACHES: AtomicUsize = AtomicUsize::new(0);
+
+    thread_local! {
+        static ATTACHED: Cell<u32> = const { Cell::new(0) };
+    }
+
+    fn sdk_thread_attach() {
+        ATTACHED.set(ATTACHED.get() + 1);
+        ATTACHES.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn sdk_thread_detach() {
+        DETACHES.fetch_add(1, Ordering::SeqCst);
+    }
+
+    struct Sdk;
+
+    #[blocking_impl(AsyncSdk, thread_init = sdk_thread_attach, thread_deinit = sdk_thread_detach)]
+    impl Sdk {
+        #[async_wrap]
+        pub fn attachments(&self) -> u32 {
+            ATTACHED.get()
+        }
+
+        #[async_wrap]
+        pub fn other_attachments(&self) -> u32 {
+            ATTACHED.get()
+        }
+    }
+
+    pub struct AsyncSdk {
+        inner: Arc<Sdk>,
+    }
+
+    #[test]
+    fn test_init_runs_once_per_thread_and_deinit_on_exit() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .build()
+            .unwrap();
+        let sdk = AsyncSdk {
+            inner: Arc::new(Sdk),
+        };
+        runtime.block_on(async {
+            for _ in 0..3 {
+                assert_eq!(sdk.attachments().await.unwrap(), 1);
+                assert_eq!(sdk.other_attachments().await.unwrap(), 1);
+            }
+            let (a, b) = tokio::join!(sdk.attachments(), sdk.attachments());
+            assert_eq!((a.unwrap(), b.unwrap()), (1, 1));
+        });
+        let attaches = ATTACHES.load(Ordering::SeqCst);
+        assert!(attaches >= 1);
+
+        runtime.shutdown_timeout(std::time::Duration::from_secs(5));
+        assert_eq!(DETACHES.load(Ordering::SeqCst), attaches);
+    }
+}
+
+mod priority {
+    use asyncwrap::{blocking_impl, Dispatcher};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct Printer {
+        printed: Mutex<Vec<u32>>,
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+    }
+
+    impl Printer {
+        fn record(&self, job: u32) {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20));
+            self.printed.lock().unwrap().push(job);
+            self.running.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[blocking_impl(AsyncPrinter, dispatcher = "dispatcher")]
+    impl Printer {
+        #[async_wrap]
+        pub fn print(&self, job: u32) {
+            self.record(job);
+        }
+
+        #[async_wrap(priority = "low")]
+        pub fn print_draft(&self, job: u32) {
+            self.record(job);
+        }
+    }
+
+    pub struct AsyncPrinter {
+        inner: Arc<Printer>,
+        dispatcher: Dispatcher,
+    }
+
+    fn printer(permits: usize) -> Arc<AsyncPrinter> {
+        Arc::new(AsyncPrinter {
+            inner: Arc::new(Printer::default()),
+            dispatcher: Dispatcher::new(permits),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_waiting_calls_start_by_priority() {
+        use asyncwrap::Priority;
+
+        let printer = printer(1);
+        let first = tokio::spawn({
+            let printer = Arc::clone(&printer);
+            async move { printer.print(0).await }
+        });
+        while printer.dispatcher.running() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let (draft, normal, urgent) = tokio::join!(
+            printer.print_draft(1),
+            printer.print(2),
+            printer.print_with_priority(Priority::High, 3),
+        );
+        first.await.unwrap().unwrap();
+        draft.unwrap();
+        normal.unwrap();
+        urgent.unwrap();
+
+        assert_eq!(*printer.inner.printed.lock().unwrap(), [0, 3, 2, 1]);
+        assert_eq!(printer.dispatcher.running(), 0);
+        assert_eq!(printer.dispatcher.waiting(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dispatcher_limits_running_calls() {
+        let printer = printer(2);
+        let results = tokio::join!(
+            printer.print(0),
+            printer.print(1),
+            printer.print_draft(2),
+            printer.print_draft(3),
+        );
+        for result in [results.0, results.1, results.2, results.3] {
+            result.unwrap();
+        }
+        assert_eq!(printer.inner.max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_gives_up_its_place() {
+        let printer = printer(1);
+        let first = tokio::spawn({
+            let printer = Arc::clone(&printer);
+            async move { printer.print(0).await }
+        });
+        while printer.dispatcher.running() == 0 {
+            tokio::task::yield_now().await;
+        }
+        let timed_out = tokio::time::timeout(Duration::from_millis(1), printer.print(1)).await;
+        assert!(timed_out.is_err());
+
+        first.await.unwrap().unwrap();
+        printer.print(2).await.unwrap();
+        assert_eq!(*printer.inner.printed.lock().unwrap(), [0, 2]);
+    }
+
+    mod observed {
+        use asyncwrap::{
+            blocking_impl, CallOutcome, CallSite, CallTiming, Dispatcher, WrapObserver,
+        };
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        struct Counts {
+            started: AtomicUsize,
+            completed: AtomicUsize,
+        }
+
+        impl WrapObserver for Counts {
+            fn on_start(&self, _: CallSite) {
+                self.started.fetch_add(1, Ordering::SeqCst);
+            }
+
+            fn on_complete(&self, _: CallSite, _: CallOutcome, _: CallTiming) {
+                self.completed.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        static COUNTS: Counts = Counts {
+            started: AtomicUsize::new(0),
+            completed: AtomicUsize::new(0),
+        };
+
+        struct Spooler;
+
+        #[blocking_impl(AsyncSpooler, dispatcher = "dispatcher", observer = COUNTS)]
+        impl Spooler {
+            #[async_wrap]
+            pub fn spool(&self, delay_ms: u64) {
+                std::thread::sleep(Duration::from_millis(delay_ms));
+            }
+        }
+
+        pub struct AsyncSpooler {
+            inner: Arc<Spooler>,
+            dispatcher: Dispatcher,
+        }
+
+        #[tokio::test]
+        async fn test_cancelled_waiter_is_not_observed() {
+            let spooler = Arc::new(AsyncSpooler {
+                inner: Arc::new(Spooler),
+                dispatcher: Dispatcher::new(1),
+            });
+            let first = tokio::spawn({
+                let spooler = Arc::clone(&spooler);
+                async move { spooler.spool(30).await }
+            });
+            while spooler.dispatcher.running() == 0 {
+                tokio::task::yield_now().await;
+            }
+            let timed_out = tokio::time::timeout(Duration::from_millis(1), spooler.spool(0)).await;
+            assert!(timed_out.is_err());
+
+            first.await.unwrap().unwrap();
+            assert_eq!(COUNTS.started.load(Ordering::SeqCst), 1);
+            assert_eq!(COUNTS.completed.load(Ordering::SeqCst), 1);
+        }
+    }
+}
+
+mod runtime_handle {
+    use asyncwrap::{blocking_impl, WrapState};
+    use std::future::Future;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use tokio::runtime::Handle;
+
+    #[derive(Default)]
+    struct Scanner {
+        attempts: AtomicU32,
+    }
+
+    #[blocking_impl(AsyncScanner, handle = "runtime")]
+    impl Scanner {
+        #[async_wrap]
+        pub fn scan(&self, page: u32) -> String {
+            format!("page-{page}")
+        }
+
+        #[async_wrap(retry(max = 3, backoff = "fixed", base = "1ms"))]
+        pub fn warm_up(&self) -> Result<u32, String> {
+            match self.attempts.fetch_add(1, Ordering::SeqCst) + 1 {
+                attempt @ 3 => Ok(attempt),
+                _ => Err("lamp cold".to_string()),
+            }
+        }
+
+        #[async_wrap(batch(with = scan_many, max_delay = "5ms"))]
+        pub fn scan_batched(&self, page: u32) -> String {
+            self.scan(page)
+        }
+
+        pub fn scan_many(&self, pages: Vec<u32>) -> Vec<String> {
+            pages.into_iter().map(|page| self.scan(page)).collect()
+        }
+    }
+
+    pub struct AsyncScanner {
+        inner: Arc<Scanner>,
+        runtime: Handle,
+        state: WrapState,
+    }
+
+    /// Polls `future` on the current thread, outside of any Tokio runtime.
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        struct Unpark(std::thread::Thread);
+
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            std::thread::park();
+        }
+    }
+
+    #[test]
+    fn test_calls_work_outside_of_the_runtime() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .unwrap();
+        let scanner = AsyncScanner {
+            inner: Arc::new(Scanner::default()),
+            runtime: runtime.handle().clone(),
+            state: WrapState::new(),
+        };
+        assert!(Handle::try_current().is_err());
+
+        assert_eq!(block_on(scanner.scan(1)).unwrap(), "page-1");
+        assert_eq!(block_on(scanner.warm_up()).unwrap(), 3);
+        assert_eq!(block_on(scanner.scan_batched(2)).unwrap(), "page-2");
+    }
+}
+
+#[cfg(feature = "mock")]
+mod mocks {
+    use asyncwrap::blocking_impl;
+    use std::sync::Arc;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct NotFound;
+
+    struct Inventory;
+
+    #[blocking_impl(AsyncInventory, generate_mock = true)]
+    impl Inventory {
+        /// Counts the items with this SKU.
+        #[async_wrap]
+        pub fn count(&self, sku: u32) -> Result<u32, NotFound> {
+            (sku != 0).then_some(sku).ok_or(NotFound)
+        }
+
+        #[async_wrap]
+        pub fn restock(&self, sku: u32, amount: u32) {
+            let _ = (sku, amount);
+        }
+    }
+
+    pub struct AsyncInventory {
+        inner: Arc<Inventory>,
+    }
+
+    struct Scale;
+
+    #[blocking_impl(AsyncScale, strategy = "block_in_place", generate_mock = true)]
+    impl Scale {
+        #[async_wrap]
+        pub fn weigh(&self) -> u32 {
+            0
+        }
+    }
+
+    pub struct AsyncScale {
+        inner: Arc<Scale>,
+    }
+
+    struct Depot;
+
+    #[blocking_impl(
+        AsyncDepot,
+        dispatcher = "dispatcher",
+        circuit_breaker(failure_threshold = 2, open_for = "1h"),
+        generate_mock = true
+    )]
+    impl Depot {
+        #[async_wrap(priority = "high", cache(ttl = "1h"))]
+        pub fn locate(&self, sku: u32) -> u32 {
+            sku
+        }
+    }
+
+    pub struct AsyncDepot {
+        inner: Arc<Depot>,
+        state: asyncwrap::WrapState,
+        dispatcher: asyncwrap::Dispatcher,
+        breaker: asyncwrap::CircuitBreaker,
+    }
+
+    #[tokio::test]
+    async fn test_expectations_answer_matching_calls() {
+        use asyncwrap::AsyncWrapError;
+
+        let mut inventory = MockAsyncInventory::new();
+        inventory
+            .expect_count()
+            .with(|&(sku,)| sku == 7)
+            .returns(Ok(3))
+            .times(1);
+        inventory.expect_count().returning(|&(sku,)| {
+            if sku == 0 {
+                Err(NotFound)
+            } else {
+                Ok(sku * 10)
+            }
+        });
+
+        assert_eq!(inventory.count(7).await.unwrap(), 3);
+        assert_eq!(inventory.count(7).await.unwrap(), 70);
+        assert!(matches!(
+            inventory.count(0).await,
+            Err(AsyncWrapError::Inner(NotFound))
+        ));
+        assert_eq!(*inventory.count_calls(), [(7,), (7,), (0,)]);
+    }
+
+    #[tokio::test]
+    async fn test_panics_and_delays() {
+        use std::time::{Duration, Instant};
+
+        let mut inventory = MockAsyncInventory::new();
+        inventory.expect_count().panics("disk on fire");
+        inventory
+            .expect_restock()
+            .returns(())
+            .delay(Duration::from_millis(20));
+
+        let failure = match inventory.count(1).await {
+            Err(asyncwrap::AsyncWrapError::TaskFailed(failure)) => failure,
+            other => panic!("expected a task failure, got {other:?}"),
+        };
+        assert_eq!(failure.panic_message(), Some("disk on fire"));
+        assert_eq!(
+            failure.call_site().unwrap().to_string(),
+            "AsyncInventory::count"
+        );
+
+        let started = Instant::now();
+        inventory.restock(1, 5).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert_eq!(*inventory.restock_calls(), [(1, 5)]);
+    }
+
+    #[tokio::test]
+    async fn test_mock_has_the_priority_cache_and_circuit_methods() {
+        use asyncwrap::{CircuitState, Priority};
+
+        let mut depot = MockAsyncDepot::new();
+        depot.expect_locate().returning(|&(sku,)| sku + 100);
+
+        assert_eq!(depot.locate(1).await.unwrap(), 101);
+        assert_eq!(
+            depot.locate_with_priority(Priority::Low, 2).await.unwrap(),
+            102
+        );
+        depot.invalidate_locate();
+        depot.clear_cache();
+        assert_eq!(*depot.locate_calls(), [(1,), (2,)]);
+
+        assert_eq!(depot.circuit_state(), CircuitState::Closed);
+        depot.set_circuit_state(CircuitState::Open);
+        assert_eq!(depot.circuit_state(), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_block_in_place_mock_keeps_the_return_type() {
+        let mut scale = MockAsyncScale::default();
+        scale.expect_weigh().returns(42);
+        assert_eq!(scale.weigh().await, 42);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "unexpected call to AsyncInventory::count")]
+    async fn test_unexpected_call_panics() {
+        let inventory = MockAsyncInventory::new();
+        let _ = inventory.count(1).await;
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 2 call(s) to AsyncInventory::restock, got 0")]
+    fn test_unmet_times_panics_on_drop() {
+        let mut inventory = MockAsyncInventory::new();
+        inventory.expect_restock().returns(()).times(2);
+    }
+}
+
+mod fault_injection {
+    use asyncwrap::blocking_impl;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct Archive {
+        reads: AtomicU32,
+    }
+
+    #[blocking_impl(AsyncArchive, fault_injection = true)]
+    impl Archive {
+        #[async_wrap]
+        pub fn read(&self, page: u32) -> u32 {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            page
+        }
+
+        #[async_wrap]
+        pub fn list(&self) -> Vec<u32> {
+            vec![1, 2]
+        }
+    }
+
+    pub struct AsyncArchive {
+        inner: Arc<Archive>,
+    }
+
+    struct Ledger;
+
+    #[blocking_impl(
+        AsyncLedger,
+        strategy = "block_in_place",
+        catch_unwind = true,
+        fault_injection = true
+    )]
+    impl Ledger {
+        #[async_wrap]
+        pub fn balance(&self) -> u32 {
+            7
+        }
+    }
+
+    pub struct AsyncLedger {
+        inner: Arc<Ledger>,
+    }
+
+    struct Press;
+
+    #[blocking_impl(AsyncPress, handle = "runtime", fault_injection = true)]
+    impl Press {
+        #[async_wrap]
+        pub fn stamp(&self, sheet: u32) -> u32 {
+            sheet
+        }
+    }
+
+    pub struct AsyncPress {
+        inner: Arc<Press>,
+        runtime: tokio::runtime::Handle,
+    }
+
+    struct Kiln;
+
+    #[blocking_impl(AsyncKiln, pool = "kilns", fault_injection = true)]
+    impl Kiln {
+        #[async_wrap]
+        pub fn fire(&self, batch: u32) -> u32 {
+            batch
+        }
+    }
+
+    pub struct AsyncKiln {
+        inner: Arc<Kiln>,
+    }
+
+    struct Vault;
+
+    #[blocking_impl(AsyncVault)]
+    impl Vault {
+        #[async_wrap]
+        pub fn open(&self) -> bool {
+            true
+        }
+    }
+
+    pub struct AsyncVault {
+        inner: Arc<Vault>,
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_injected_panic_fails_calls_without_running_them() {
+        use asyncwrap::testing::{self, Fault};
+        use asyncwrap::AsyncWrapError;
+
+        let archive = AsyncArchive {
+            inner: Arc::new(Archive::default()),
+        };
+        let fault = testing::inject_into("AsyncArchive", Fault::panic("disk failure").times(1));
+
+        let Err(AsyncWrapError::TaskFailed(failure)) = archive.read(1).await else {
+            panic!("expected an injected failure");
+        };
+        assert!(failure.is_panic());
+        assert_eq!(failure.panic_message(), Some("disk failure"));
+        assert_eq!(
+            failure.call_site().unwrap().to_string(),
+            "AsyncArchive::read"
+        );
+        assert_eq!(archive.inner.reads.load(Ordering::SeqCst), 0);
+
+        assert_eq!(archive.read(2).await.unwrap(), 2);
+        assert_eq!(archive.inner.reads.load(Ordering::SeqCst), 1);
+        drop(fault);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_injected_cancellation_targets_one_method_until_dropped() {
+        use asyncwrap::testing::{self, Fault};
+
+        let ledger = AsyncLedger {
+            inner: Arc::new(Ledger),
+        };
+        let fault = testing::inject_into_method("AsyncLedger", "balance", Fault::cancel());
+        for _ in 0..2 {
+            let failure = ledger.balance().await.unwrap_err();
+            assert!(failure.is_cancelled());
+        }
+        drop(fault);
+        assert_eq!(ledger.balance().await.unwrap(), 7);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_injected_delay_slows_calls_down() {
+        use asyncwrap::testing::{self, Fault};
+        use std::time::{Duration, Instant};
+
+        let archive = AsyncArchive {
+            inner: Arc::new(Archive::default()),
+        };
+        let _fault = testing::inject_into_method(
+            "AsyncArchive",
+            "list",
+            Fault::delay(Duration::from_millis(30)),
+        );
+        let started = Instant::now();
+        assert_eq!(archive.list().await.unwrap(), [1, 2]);
+        assert!(started.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_injected_delay_uses_the_wrapper_runtime() {
+        use asyncwrap::testing::{self, Fault};
+        use std::time::{Duration, Instant};
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .unwrap();
+        let press = AsyncPress {
+            inner: Arc::new(Press),
+            runtime: runtime.handle().clone(),
+        };
+        let _fault = testing::inject_into("AsyncPress", Fault::delay(Duration::from_millis(30)));
+
+        let started = Instant::now();
+        assert_eq!(super::runtime_handle::block_on(press.stamp(4)).unwrap(), 4);
+        assert!(started.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_injected_delay_of_a_pool_wrapper_uses_the_caller_runtime() {
+        use asyncwrap::testing::{self, Fault};
+        use std::time::{Duration, Instant};
+
+        asyncwrap::PoolBuilder::new("kilns").register().unwrap();
+        let kiln = AsyncKiln {
+            inner: Arc::new(Kiln),
+        };
+        let _fault = testing::inject_into("AsyncKiln", Fault::delay(Duration::from_millis(30)));
+
+        let started = Instant::now();
+        assert_eq!(kiln.fire(5).await.unwrap(), 5);
+        assert!(started.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_wrappers_without_fault_injection_ignore_faults() {
+        use asyncwrap::testing::{self, Fault};
+
+        let vault = AsyncVault {
+            inner: Arc::new(Vault),
+        };
+        let _fault = testing::inject_into("AsyncVault", Fault::panic("vault jammed"));
+        assert!(vault.open().await.unwrap());
+    }
+
+    #[cfg(not(feature = "testing"))]
+    #[tokio::test]
+    async fn test_fault_injection_is_a_no_op_without_the_testing_feature() {
+        let archive = AsyncArchive {
+            inner: Arc::new(Archive::default()),
+        };
+        assert_eq!(archive.read(3).await.unwrap(), 3);
+        assert_eq!(archive.inner.reads.load(Ordering::SeqCst), 1);
+    }
+}
+
+mod visibility {
+    use super::*;
+
+    struct VisService;
+
+    #[blocking_impl(AsyncVisService)]
+    impl VisService {
+        #[async_wrap]
+        pub fn public_fn(&self) -> i32 {
+            1
+        }
+
+        #[async_wrap]
+        pub(crate) fn crate_fn(&self) -> i32 {
+            2
+        }
+    }
+
+    pub struct AsyncVisService {
+        inner: Arc<VisService>,
+    }
+
+    #[tokio::test]
+    async fn test_visibility_preserved() {
+        let svc = AsyncVisService {
+            inner: Arc::new(VisService),
+        };
+        assert_eq!(svc.public_fn().await.unwrap(), 1);
+        assert_eq!(svc.crate_fn().await.unwrap(), 2);
+    }
+}
+
+mod task_panic {
+    use super::*;
+    use thiserror::Error;
+
+    #[derive(Error, Debug)]
+    #[error("inner error")]
+    struct InnerError;
+
+    struct PanickingService;
+
+    #[blocking_impl(AsyncPanickingService)]
+    impl PanickingService {
+        #[async_wrap]
+        pub fn will_panic(&self) -> i32 {
+            panic!("intentional panic for testing");
+        }
+
+        #[async_wrap]
+        #[allow(clippy::unnecessary_wraps, clippy::manual_assert)]
+        pub fn might_panic(&self, should_panic: bool) -> Result<i32, InnerError> {
+            if should_panic {
+                panic!("intentional panic for testing");
+            }
+            Ok(42)
+        }
+    }
+
+    pub struct AsyncPanickingService {
+        inner: Arc<PanickingService>,
+    }
+
+    #[tokio::test]
+    async fn test_panic_returns_join_error() {
+        let svc = AsyncPanickingService {
+            inner: Arc::new(PanickingService),
+        };
+
+        let result = svc.will_panic().await;
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(err.is_panic());
+    }
+
+    #[tokio::test]
+    async fn test_result_panic_returns_task_failed() {
+        let svc = AsyncPanickingService {
+            inner: Arc::new(PanickingService),
+        };
+
+        let result = svc.might_panic(true).await;
+        assert!(result.is_err());
+        assert!(matches!(
+            result.unwrap_err(),
+            asyncwrap::AsyncWrapError::TaskFailed(_)
         ));
     }
 
+    #[tokio::test]
+    async fn test_panic_message_and_call_site() {
+        let svc = AsyncPanickingService {
+            inner: Arc::new(PanickingService),
+        };
+
+        let err = svc.might_panic(true).await.unwrap_err();
+        assert!(err.is_panic());
+        assert!(!err.is_cancelled());
+        assert_eq!(err.panic_message(), Some("intentional panic for testing"));
+
+        let call_site = err.call_site().unwrap();
+        assert_eq!(call_site.type_name(), "AsyncPanickingService");
+        assert_eq!(call_site.method(), "might_panic");
+        assert_eq!(
+            err.to_string(),
+            "async task failed: `AsyncPanickingService::might_panic` panicked: \
+             intentional panic for testing"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_into_panic_returns_payload() {
+        let svc = AsyncPanickingService {
+            inner: Arc::new(PanickingService),
+        };
+
+        let payload = svc.will_panic().await.unwrap_err().into_panic().unwrap();
+        assert_eq!(
+            payload.downcast_ref::<&str>(),
+            Some(&"intentional panic for testing")
+        );
+    }
+
     #[tokio::test]
     async fn test_result_no_panic_returns_inner() {
         let svc = AsyncPanickingService {
@@ -0,0 +1,18 @@
+use asyncwrap::async_impl;
+
+struct AsyncClient;
+
+#[async_impl(BlockingClient)]
+impl AsyncClient {
+    #[blocking_wrap]
+    pub fn count(&self) -> u32 {
+        42
+    }
+}
+
+pub struct BlockingClient {
+    inner: AsyncClient,
+    runtime: tokio::runtime::Runtime,
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use asyncwrap::{blocking_impl, WrapState};
+
+struct BlockingScale;
+
+#[blocking_impl(AsyncScale)]
+impl BlockingScale {
+    #[async_wrap(coalesce)]
+    pub fn weigh(&self, tare: f64) -> f64 {
+        tare
+    }
+}
+
+pub struct AsyncScale {
+    inner: std::sync::Arc<BlockingScale>,
+    state: WrapState,
+}
+
+fn main() {}
@@ -0,0 +1,21 @@
+use std::os::raw::c_int;
+
+pub struct Handle;
+
+fn check(code: c_int) -> Result<(), c_int> {
+    if code == 0 {
+        Ok(())
+    } else {
+        Err(code)
+    }
+}
+
+asyncwrap::wrap_extern! {
+    Device, AsyncDevice, handle = Handle, code = c_int, check = check, error = c_int,
+    strategy = "spawn_blocking", handle = "runtime"
+    extern "C" {
+        fn device_reset(handle: *mut Handle) -> c_int;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,17 @@
+use asyncwrap::blocking_impl;
+
+struct BlockingClient;
+
+#[blocking_impl(AsyncClient, field = "client")]
+impl BlockingClient {
+    #[async_wrap]
+    pub fn ping(&self) -> u32 {
+        1
+    }
+}
+
+pub struct AsyncClient {
+    client: Box<BlockingClient>,
+}
+
+fn main() {}
@@ -0,0 +1,23 @@
+use asyncwrap::blocking_impl;
+use std::sync::Arc;
+
+struct BlockingClient;
+
+#[blocking_impl(AsyncClient, field = "client")]
+impl BlockingClient {
+    #[async_wrap]
+    pub fn ping(&self) -> u32 {
+        1
+    }
+
+    #[async_wrap]
+    pub fn pong(&self) -> u32 {
+        2
+    }
+}
+
+pub struct AsyncClient {
+    inner: Arc<BlockingClient>,
+}
+
+fn main() {}
@@ -0,0 +1,22 @@
+use asyncwrap::blocking_impl;
+
+struct BlockingClient;
+
+#[blocking_impl(AsyncClient)]
+impl BlockingClient {
+    #[async_wrap]
+    pub fn ping(&self) -> u32 {
+        1
+    }
+
+    #[async_wrap]
+    pub fn pong(&self) -> u32 {
+        2
+    }
+}
+
+pub struct AsyncClient {
+    inner: BlockingClient,
+}
+
+fn main() {}
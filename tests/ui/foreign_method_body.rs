@@ -0,0 +1,23 @@
+use asyncwrap::wrap_foreign;
+
+struct Connection;
+
+impl Connection {
+    fn ping(&self) -> bool {
+        true
+    }
+}
+
+wrap_foreign! {
+    AsyncConn for Connection {
+        fn ping(&self) -> bool {
+            true
+        }
+    }
+}
+
+pub struct AsyncConn {
+    inner: std::sync::Arc<Connection>,
+}
+
+fn main() {}
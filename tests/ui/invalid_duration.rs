@@ -0,0 +1,17 @@
+use asyncwrap::blocking_impl;
+
+struct BlockingClient;
+
+#[blocking_impl(AsyncClient)]
+impl BlockingClient {
+    #[async_wrap(retry(max = 3, base = "10 parsecs"))]
+    pub fn fetch(&self) -> Result<u32, std::io::Error> {
+        Ok(42)
+    }
+}
+
+pub struct AsyncClient {
+    inner: std::sync::Arc<BlockingClient>,
+}
+
+fn main() {}
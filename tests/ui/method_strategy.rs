@@ -0,0 +1,17 @@
+use asyncwrap::blocking_impl;
+
+struct BlockingClient;
+
+#[blocking_impl(AsyncClient)]
+impl BlockingClient {
+    #[async_wrap(strategy = "block_in_place")]
+    pub fn count(&self) -> u32 {
+        42
+    }
+}
+
+pub struct AsyncClient {
+    inner: std::sync::Arc<BlockingClient>,
+}
+
+fn main() {}
@@ -0,0 +1,23 @@
+use asyncwrap::blocking_impl;
+use std::rc::Rc;
+
+struct BlockingClient;
+
+#[blocking_impl(AsyncClient)]
+impl BlockingClient {
+    #[async_wrap]
+    pub fn name(&self, id: u64) -> Rc<String> {
+        Rc::new(id.to_string())
+    }
+}
+
+pub struct AsyncClient {
+    inner: std::sync::Arc<BlockingClient>,
+}
+
+fn main() {
+    // This should fail because the returned Rc is not Send
+    let _ = AsyncClient {
+        inner: std::sync::Arc::new(BlockingClient),
+    };
+}
@@ -0,0 +1,19 @@
+use asyncwrap::{blocking_impl, SlowCall};
+
+struct BlockingClient;
+
+fn report(_call: &SlowCall) {}
+
+#[blocking_impl(AsyncClient)]
+impl BlockingClient {
+    #[async_wrap(on_slow = report)]
+    pub fn count(&self) -> u32 {
+        42
+    }
+}
+
+pub struct AsyncClient {
+    inner: std::sync::Arc<BlockingClient>,
+}
+
+fn main() {}
@@ -0,0 +1,20 @@
+use asyncwrap::blocking_impl;
+
+pub struct Command;
+
+struct BlockingClient;
+
+#[blocking_impl(AsyncClient)]
+impl BlockingClient {
+    #[async_wrap(retry(max = 3))]
+    pub fn send(&self, id: u32, command: Command) -> Result<(), std::io::Error> {
+        let _ = (id, command);
+        Ok(())
+    }
+}
+
+pub struct AsyncClient {
+    inner: std::sync::Arc<BlockingClient>,
+}
+
+fn main() {}
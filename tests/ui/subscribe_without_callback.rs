@@ -0,0 +1,17 @@
+use asyncwrap::blocking_impl;
+
+struct BlockingFeed;
+
+#[blocking_impl(AsyncFeed)]
+impl BlockingFeed {
+    #[async_wrap(subscribe(overflow = "block"))]
+    pub fn subscribe(&self, topic: String) -> u64 {
+        topic.len() as u64
+    }
+}
+
+pub struct AsyncFeed {
+    inner: std::sync::Arc<BlockingFeed>,
+}
+
+fn main() {}
@@ -0,0 +1,20 @@
+use asyncwrap::blocking_impl;
+use std::cell::Cell;
+
+struct BlockingClient {
+    hits: Cell<u32>,
+}
+
+#[blocking_impl(AsyncClient, strategy = "block_in_place", catch_unwind = true)]
+impl BlockingClient {
+    #[async_wrap(assert_unwind_safe = false)]
+    pub fn hit(&self) -> u32 {
+        self.hits.get()
+    }
+}
+
+pub struct AsyncClient {
+    inner: BlockingClient,
+}
+
+fn main() {}